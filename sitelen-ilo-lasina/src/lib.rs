@@ -0,0 +1,89 @@
+//! Transliteration between *sitelen Lasina* and *sitelen pona*, using the UCSUR encoding.
+
+use std::fmt::Display;
+
+pub mod tables;
+
+mod parser;
+mod reverse;
+mod source;
+
+pub use reverse::to_lasina;
+pub use source::{Transliteration, source_to_lasina, source_to_ucsur};
+
+/// Error for *sitelen Lasina* that can't be transliterated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LasinaError {
+    /// Offset of the unknown word from the start of the input, in bytes.
+    pub offset: usize,
+    /// The unknown word.
+    pub word: String,
+}
+impl Display for LasinaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown word \"{}\" at byte {}", self.word, self.offset)
+    }
+}
+impl std::error::Error for LasinaError {}
+
+/// Translates *sitelen Lasina* to *sitelen pona* by the UCSUR encoding. Whitespace is dropped,
+/// and text that is already in *sitelen pona* is kept as is.
+pub fn to_ucsur(input: &str) -> Result<String, LasinaError> {
+    let mut out = String::new();
+    let mut rest = input;
+    loop {
+        (rest, _) = parser::ws(rest).expect("whitespace never fails to parse");
+        if rest.is_empty() {
+            return Ok(out);
+        }
+        match parser::translatable(rest) {
+            Ok((rest1, c)) => {
+                out.push(c);
+                rest = rest1;
+            }
+            Err(_) => {
+                return Err(LasinaError {
+                    offset: input.len() - rest.len(),
+                    word: parser::token(rest).to_string(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LasinaError, to_ucsur};
+
+    #[test]
+    fn test_to_ucsur() {
+        assert_eq!(to_ucsur("toki pona").unwrap(), "\u{F196C}\u{F1954}");
+        assert_eq!(
+            to_ucsur(" jan [ijo] <a>.").unwrap(),
+            "\u{F1911}\u{F1990}\u{F190C}\u{F1991}「\u{F1900}」\u{F199C}"
+        );
+        // already transliterated
+        assert_eq!(to_ucsur("\u{F1911} pona").unwrap(), "\u{F1911}\u{F1954}");
+        assert_eq!(to_ucsur("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_failures() {
+        assert_eq!(
+            to_ucsur("toki hello"),
+            Err(LasinaError {
+                offset: 5,
+                word: "hello".into()
+            })
+        );
+        assert_eq!(
+            to_ucsur("jan Pona"),
+            Err(LasinaError {
+                offset: 4,
+                word: "Pona".into()
+            })
+        );
+        // words must be whole
+        assert_eq!(to_ucsur("tokii").unwrap_err().offset, 0);
+    }
+}
@@ -1,21 +1,30 @@
 use nom::{
-    branch::alt, bytes::complete::{take, take_till}, error::Error, multi::{fold, separated_list0}, Parser
+    Parser,
+    branch::alt,
+    bytes::complete::{take, take_till},
+    character::complete::satisfy,
+    error::Error,
 };
 
 use crate::tables::{PUNCT_TABLE, SP_TABLE};
 
-
 pub(crate) fn ws(input: &str) -> nom::IResult<&str, &str> {
     take_till(|c: char| !c.is_whitespace())(input)
 }
 
+/// Takes the text up to the next space, for error messages.
+pub(crate) fn token(input: &str) -> &str {
+    let len = input.find(char::is_whitespace).unwrap_or(input.len());
+    &input[..len]
+}
+
 pub(crate) fn word(input: &str) -> nom::IResult<&str, char> {
     let (input1, word) = take_till(|c: char| !c.is_ascii_lowercase())(input)?;
 
     let c = *SP_TABLE
         .get(word)
         .ok_or_else(|| nom::Err::Error(Error::new(input, nom::error::ErrorKind::OneOf)))?;
-    return Ok((input1, c));
+    Ok((input1, c))
 }
 
 pub(crate) fn punct(input: &str) -> nom::IResult<&str, char> {
@@ -26,14 +35,11 @@ pub(crate) fn punct(input: &str) -> nom::IResult<&str, char> {
         .map(|c| (input1, c))
 }
 
-pub(crate) fn translatable(input: &str) -> nom::IResult<&str, char> {
-    alt([
-        word,
-        punct
-    ]).parse(input)
+/// Accepts text that is already in *sitelen pona*.
+pub(crate) fn glyph(input: &str) -> nom::IResult<&str, char> {
+    satisfy(|c| matches!(c, '\u{F1900}'..='\u{F19FF}' | '「' | '」')).parse(input)
 }
 
-pub(crate) fn sitelen_lasina(input: &str) -> nom::IResult<&str, String> {
-    let (input, _) = ws(input)?;
-    separated_list0(ws, translatable).parse(input).map(|(input, chars)| (input, chars.into_iter().collect::<String>()))
-}
\ No newline at end of file
+pub(crate) fn translatable(input: &str) -> nom::IResult<&str, char> {
+    alt([word, punct, glyph]).parse(input)
+}
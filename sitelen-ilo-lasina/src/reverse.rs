@@ -0,0 +1,102 @@
+//! Transliteration from *sitelen pona* back to *sitelen Lasina*.
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use crate::tables::{PUNCT_TABLE, SP_TABLE};
+
+/// What a character turns back into.
+#[derive(Debug, Clone, Copy)]
+enum Reverse {
+    Word(&'static str),
+    Punct(&'static str),
+}
+
+/// Table mapping sitelen pona characters back to the text they come from.
+static REVERSE_TABLE: LazyLock<HashMap<char, Reverse>> = LazyLock::new(|| {
+    let words = SP_TABLE.entries().map(|(k, v)| (*v, Reverse::Word(k)));
+    let puncts = PUNCT_TABLE.entries().map(|(k, v)| (*v, Reverse::Punct(k)));
+    words.chain(puncts).collect()
+});
+
+/// What was last written, which decides whether a space is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Last {
+    /// Nothing, whitespace, or an opening bracket.
+    Break,
+    /// A word or a closing bracket.
+    Word,
+    /// Other text.
+    Other,
+}
+
+/// Translates *sitelen pona* to *sitelen Lasina*. Words are separated by spaces, and text that is
+/// not *sitelen pona* is kept as is.
+pub fn to_lasina(input: &str) -> String {
+    let mut out = String::new();
+    let mut last = Last::Break;
+    for c in input.chars() {
+        match REVERSE_TABLE.get(&c) {
+            Some(Reverse::Word(word)) => {
+                if last != Last::Break {
+                    out.push(' ');
+                }
+                out.push_str(word);
+                last = Last::Word;
+            }
+            Some(Reverse::Punct(punct @ ("[" | "<"))) => {
+                if last == Last::Word {
+                    out.push(' ');
+                }
+                out.push_str(punct);
+                last = Last::Break;
+            }
+            Some(Reverse::Punct(punct)) => {
+                out.push_str(punct);
+                last = Last::Word;
+            }
+            None => {
+                // keep words from running into Latin text
+                if last == Last::Word && c.is_alphanumeric() {
+                    out.push(' ');
+                }
+                out.push(c);
+                last = match c.is_whitespace() {
+                    true => Last::Break,
+                    false => Last::Other,
+                };
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{reverse::to_lasina, to_ucsur};
+
+    #[test]
+    fn test_to_lasina() {
+        let cases = [
+            "toki pona",
+            "nanpa [ijo] o sin",
+            "ilo o toki e toki <jan pona>",
+            "nanpa [ijo] li suli tawa nanpa <luka> la sike o pini",
+            "ala la [jan] o pali.",
+        ];
+        for case in cases {
+            let ucsur = to_ucsur(case).unwrap();
+            assert_eq!(to_lasina(&ucsur), case);
+        }
+    }
+
+    #[test]
+    fn test_passthrough() {
+        assert_eq!(to_lasina("\u{F196C}「hello」"), "toki <hello>");
+        assert_eq!(
+            to_lasina("\u{F1925}　toki pona\n\u{F1911}"),
+            "len　toki pona\njan"
+        );
+        assert_eq!(to_lasina("\u{F1911}x"), "jan x");
+        assert_eq!(to_lasina("#!/usr/bin/env o-ilo"), "#!/usr/bin/env o-ilo");
+    }
+}
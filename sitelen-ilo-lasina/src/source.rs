@@ -0,0 +1,337 @@
+//! Transliteration of whole *sitelen ilo* programs written in *sitelen Lasina*.
+
+use std::ops::Range;
+
+use crate::{
+    LasinaError, parser,
+    reverse::to_lasina,
+    tables::{PUNCT_TABLE, SP_TABLE},
+};
+
+/// A program transliterated to *sitelen pona*, remembering where each character came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transliteration {
+    /// The program in *sitelen pona*.
+    pub text: String,
+    /// For each character of `text`, its offset in `text` and the range it came from.
+    origins: Vec<(usize, Range<usize>)>,
+    /// Length of the original program.
+    source_len: usize,
+}
+impl Transliteration {
+    fn push(&mut self, c: char, origin: Range<usize>) {
+        self.origins.push((self.text.len(), origin));
+        self.text.push(c);
+    }
+
+    /// Copies text over as is, starting at `offset` in the original program.
+    fn push_verbatim(&mut self, text: &str, offset: usize) {
+        for (i, c) in text.char_indices() {
+            self.push(c, offset + i..offset + i + c.len_utf8());
+        }
+    }
+
+    /// Finds the index of the character containing `offset` in `text`.
+    fn find(&self, offset: usize) -> Option<usize> {
+        let i = self.origins.partition_point(|(start, _)| *start <= offset);
+        i.checked_sub(1)
+    }
+
+    /// Maps a byte range in `text` back to the original program.
+    pub fn source_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = match self.find(range.start) {
+            Some(i) if range.start < self.text.len() => self.origins[i].1.start,
+            _ => self.source_len,
+        };
+        let end = match range.end > range.start {
+            true => self
+                .find(range.end - 1)
+                .map_or(start, |i| self.origins[i].1.end),
+            false => start,
+        };
+        start..end.max(start)
+    }
+}
+
+/// Transliterates the words in `input`, which starts at `offset` in the original program.
+fn words(out: &mut Transliteration, input: &str, offset: usize) -> Result<(), LasinaError> {
+    let mut rest = input;
+    loop {
+        (rest, _) = parser::ws(rest).expect("whitespace never fails to parse");
+        if rest.is_empty() {
+            return Ok(());
+        }
+        let start = offset + input.len() - rest.len();
+        let Ok((rest1, c)) = parser::translatable(rest) else {
+            return Err(LasinaError {
+                offset: start,
+                word: parser::token(rest).to_string(),
+            });
+        };
+        out.push(c, start..start + rest.len() - rest1.len());
+        rest = rest1;
+    }
+}
+
+/// Finds the quote that closes a literal whose content starts at `start`. Doubled quotes are
+/// escapes, and don't close it.
+fn closing_quote(text: &str, start: usize) -> Option<usize> {
+    let mut pos = start;
+    loop {
+        let close = pos + text[pos..].find('>')?;
+        match text[close + 1..].starts_with('>') {
+            true => pos = close + 2,
+            false => return Some(close),
+        }
+    }
+}
+
+/// Checks whether a literal after `before` holds text, which is when its type is *toki*.
+fn is_text_literal(before: &str) -> bool {
+    let before = before.trim_end();
+    let last = before.rsplit(|c: char| !c.is_ascii_lowercase()).next();
+    last == Some("toki") || before.ends_with(SP_TABLE["toki"])
+}
+
+/// Copies the content of a text literal, which starts at `offset` in the program. Only the
+/// quotes in escapes are transliterated.
+fn text_literal(out: &mut Transliteration, content: &str, offset: usize) {
+    for (i, c) in content.char_indices() {
+        let origin = offset + i..offset + i + c.len_utf8();
+        match c {
+            '<' | '>' => out.push(PUNCT_TABLE[&content[i..i + 1]], origin),
+            _ => out.push(c, origin),
+        }
+    }
+}
+
+/// Transliterates one line, without its line break. `offset` is where it starts in the program.
+fn line(out: &mut Transliteration, text: &str, offset: usize) -> Result<(), LasinaError> {
+    let indent = text.len() - text.trim_start().len();
+    out.push_verbatim(&text[..indent], offset);
+
+    let mut pos = indent;
+    // len: the rest of the line is a comment
+    if let Some(comment) = text[pos..].strip_prefix("len")
+        && !comment.starts_with(|c: char| c.is_ascii_lowercase())
+    {
+        words(out, "len", offset + pos)?;
+        out.push_verbatim(comment, offset + pos + 3);
+        return Ok(());
+    }
+
+    while let Some(open) = text[pos..].find('<').map(|i| pos + i) {
+        words(out, &text[pos..open], offset + pos)?;
+        let Some(close) = closing_quote(text, open + 1) else {
+            pos = open;
+            break;
+        };
+
+        let content = &text[open + 1..close];
+        words(out, "<", offset + open)?;
+        match is_text_literal(&text[..open]) {
+            true => text_literal(out, content, offset + open + 1),
+            false => words(out, content, offset + open + 1)?,
+        }
+        words(out, ">", offset + close)?;
+        pos = close + 1;
+    }
+    words(out, &text[pos..], offset + pos)
+}
+
+/// Transliterates a program written in *sitelen Lasina*, following the same conventions as
+/// `sp!`. Line breaks, indentation, comments and a leading `#!` line are kept as they are.
+///
+/// Literals after *toki* hold text, so they are always kept as they are written, apart from the
+/// escapes `<<` and `>>`. Other literals hold numbers or truth values written in words, so they
+/// are always transliterated.
+pub fn source_to_ucsur(input: &str) -> Result<Transliteration, LasinaError> {
+    let mut out = Transliteration {
+        text: String::new(),
+        origins: Vec::new(),
+        source_len: input.len(),
+    };
+
+    let mut offset = 0;
+    for text in input.split_inclusive('\n') {
+        let content = text.trim_end_matches(['\n', '\r']);
+        match offset == 0 && content.starts_with("#!") {
+            true => out.push_verbatim(content, offset),
+            false => line(&mut out, content, offset)?,
+        }
+        out.push_verbatim(&text[content.len()..], offset + content.len());
+        offset += text.len();
+    }
+    Ok(out)
+}
+
+/// Finds the quote that closes a literal in *sitelen pona* whose content starts at `start`.
+fn closing_glyph_quote(text: &str, start: usize) -> Option<usize> {
+    let mut pos = start;
+    loop {
+        let close = pos + text[pos..].find('」')?;
+        match text[close + '」'.len_utf8()..].starts_with('」') {
+            true => pos = close + 2 * '」'.len_utf8(),
+            false => return Some(close),
+        }
+    }
+}
+
+/// Transliterates one line of a program back to *sitelen Lasina*.
+fn line_to_lasina(out: &mut String, text: &str) {
+    let indent = text.len() - text.trim_start().len();
+    out.push_str(&text[..indent]);
+
+    let mut rest = &text[indent..];
+    if let Some(comment) = rest.strip_prefix(SP_TABLE["len"]) {
+        out.push_str("len");
+        out.push_str(comment);
+        return;
+    }
+
+    let mut pos = 0;
+    while let Some(open) = rest[pos..].find('「').map(|i| pos + i) {
+        let content = open + '「'.len_utf8();
+        let Some(close) = closing_glyph_quote(rest, content) else {
+            break;
+        };
+        // only text is kept as it is; numbers and truth values are words
+        if !rest[..open].ends_with(SP_TABLE["toki"]) {
+            pos = close;
+            continue;
+        }
+        out.push_str(&to_lasina(&rest[..content]));
+        for c in rest[content..close].chars() {
+            match c {
+                '「' => out.push('<'),
+                '」' => out.push('>'),
+                _ => out.push(c),
+            }
+        }
+        rest = &rest[close..];
+        pos = 0;
+    }
+    out.push_str(&to_lasina(rest));
+}
+
+/// Transliterates a program back to *sitelen Lasina*, so that [`source_to_ucsur`] gives the same
+/// program. Unlike [`to_lasina`], the text of comments and *toki* literals is kept as it is.
+pub fn source_to_lasina(input: &str) -> String {
+    let mut out = String::new();
+    for (i, text) in input.split_inclusive('\n').enumerate() {
+        let content = text.trim_end_matches(['\n', '\r']);
+        match i == 0 && content.starts_with("#!") {
+            true => out.push_str(content),
+            false => line_to_lasina(&mut out, content),
+        }
+        out.push_str(&text[content.len()..]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        LasinaError,
+        source::{source_to_lasina, source_to_ucsur},
+        to_ucsur,
+    };
+
+    #[test]
+    fn test_source() {
+        let program =
+            "#!/usr/bin/env o-ilo\nlen  toki pona!\no pali\n\tilo o toki e toki <hello>\r\npini";
+        let out = source_to_ucsur(program).unwrap();
+        let expected = [
+            "#!/usr/bin/env o-ilo\n".to_string(),
+            to_ucsur("len").unwrap() + "  toki pona!\n",
+            to_ucsur("o pali").unwrap() + "\n",
+            "\t".to_string() + &to_ucsur("ilo o toki e toki").unwrap() + "「hello」\r\n",
+            to_ucsur("pini").unwrap(),
+        ];
+        assert_eq!(out.text, expected.concat());
+
+        let out = source_to_ucsur("nanpa [ijo] o nanpa <luka wan>").unwrap();
+        assert_eq!(
+            out.text,
+            to_ucsur("nanpa [ijo] o nanpa <luka wan>").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        // text is kept as written, even if it is made of words
+        for (lasina, text) in [
+            ("toki <jan pona>", "jan pona"),
+            ("toki <hello world>", "hello world"),
+            ("toki <a>", "a"),
+            ("toki <\u{F1911} x>", "\u{F1911} x"),
+            ("toki <a>>b<<c>", "a」」b「「c"),
+            ("toki<>", ""),
+        ] {
+            let out = source_to_ucsur(&format!("ilo o toki e {}", lasina)).unwrap();
+            let expected = to_ucsur("ilo o toki e toki").unwrap() + "「" + text + "」";
+            assert_eq!(out.text, expected);
+        }
+
+        // numbers and truth values are always words
+        let out = source_to_ucsur("o sona e nanpa <luka tu> o sona e lon <ala>").unwrap();
+        assert_eq!(
+            out.text,
+            to_ucsur("o sona e nanpa <luka tu> o sona e lon <ala>").unwrap()
+        );
+        assert_eq!(
+            source_to_ucsur("o sona e nanpa <five>"),
+            Err(LasinaError {
+                offset: 16,
+                word: "five".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_source_range() {
+        let program = "o pali\n  ilo o toki e toki <hello>";
+        let out = source_to_ucsur(program).unwrap();
+        let find = |lasina: &str| {
+            let ucsur = to_ucsur(lasina).unwrap();
+            let start = out.text.find(&ucsur).unwrap();
+            let range = out.source_range(start..start + ucsur.len());
+            &program[range]
+        };
+        assert_eq!(find("o toki"), "o toki");
+        assert_eq!(find("e toki"), "e toki");
+        assert_eq!(find("pali"), "pali");
+
+        let start = out.text.find("hello").unwrap();
+        assert_eq!(&program[out.source_range(start..start + 5)], "hello");
+        // the end of the program
+        let end = out.text.len();
+        assert_eq!(out.source_range(end..end), program.len()..program.len());
+    }
+
+    #[test]
+    fn test_failures() {
+        assert_eq!(
+            source_to_ucsur("o pali\nilo o tok"),
+            Err(LasinaError {
+                offset: 13,
+                word: "tok".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_source_to_lasina() {
+        let programs = [
+            "#!/usr/bin/env o-ilo\nlen\u{3000}toki pona!\no pali\n\tilo o toki e toki <hello>\r\npini",
+            "ilo o toki e toki <jan pona> e toki <\u{F1911} x> e nanpa <luka tu>",
+            "ilo o toki e toki <a>>b<<c> e toki <> e lon <ala> e toki <lon>",
+            "toki [ijo] li toki <jan> la ilo o toki e toki [ijo]",
+        ];
+        for program in programs {
+            let ucsur = source_to_ucsur(program).unwrap().text;
+            assert_eq!(source_to_lasina(&ucsur), program);
+        }
+    }
+}
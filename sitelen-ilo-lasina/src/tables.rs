@@ -1,7 +1,7 @@
 use phf::phf_map;
 
 /// Table mapping sitelen Lasina words to sitelen pona characters.
-pub(crate) static SP_TABLE: phf::Map<&'static str, char> = phf_map! {
+pub static SP_TABLE: phf::Map<&'static str, char> = phf_map! {
     "a" => '\u{F1900}',
     "akesi" => '\u{F1901}',
     "ala" => '\u{F1902}',
@@ -145,11 +145,11 @@ pub(crate) static SP_TABLE: phf::Map<&'static str, char> = phf_map! {
     "powe" => '\u{F19A3}',
 };
 
-pub(crate) static PUNCT_TABLE: phf::Map<&'static str, char> = phf_map! {
+pub static PUNCT_TABLE: phf::Map<&'static str, char> = phf_map! {
     "[" => '\u{F1990}',
     "]" => '\u{F1991}',
     "." => '\u{F199C}',
     ":" => '\u{F199D}',
     "<" => '「',
     ">" => '」',
-};
\ No newline at end of file
+};
@@ -1,22 +1,24 @@
 use proc_macro::TokenStream;
-use proc_macro2::{Literal, Span};
-use quote::{ToTokens, quote};
+use proc_macro2::Span;
+use quote::ToTokens;
+use sitelen_ilo_lasina::tables::{PUNCT_TABLE, SP_TABLE};
 use syn::{LitChar, LitStr};
 
-use crate::tables::{PUNCT_TABLE, SP_TABLE};
-
-mod parser;
-mod tables;
-
 /// Translates *sitelen Lasina* to *sitelen pona* by the UCSUR encoding.
 #[proc_macro]
 pub fn sp(tokens: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(tokens as LitStr);
     let lasina = input.value();
-    let parse_result =
-        parser::sitelen_lasina(&lasina).expect("string should parse as sitelen Lasina");
+    let ucsur = match sitelen_ilo_lasina::to_ucsur(&lasina) {
+        Ok(ucsur) => ucsur,
+        Err(err) => {
+            return syn::Error::new(input.span(), err)
+                .to_compile_error()
+                .into();
+        }
+    };
 
-    let out_literal = LitStr::new(&parse_result.1, Span::call_site());
+    let out_literal = LitStr::new(&ucsur, Span::call_site());
     out_literal.to_token_stream().into()
 }
 
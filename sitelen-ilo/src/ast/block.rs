@@ -1,4 +1,4 @@
-use crate::ast::statement::{BlockStart, Command, Comment, ConditionEval};
+use crate::ast::statement::{BlockStart, Command, Comment, ConditionEval, LoopControl};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct IfBlock {
@@ -14,7 +14,7 @@ pub(crate) struct IfLadder {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct LoopBlock {
     pub(crate) start: BlockStart,
-    pub(crate) contents: Vec<Box<Statement>>
+    pub(crate) contents: Vec<Statement>
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,4 +24,18 @@ pub(crate) enum Statement {
     ConditionEval(ConditionEval),
     IfLadder(IfLadder),
     LoopBlock(LoopBlock),
-}
\ No newline at end of file
+    LoopControl(LoopControl),
+}
+impl Statement {
+    /// Finds where this statement starts in the source.
+    pub(crate) fn offset(&self) -> usize {
+        match self {
+            Statement::Comment(comment) => comment.span.offset,
+            Statement::Command(cmd) => cmd.span.offset,
+            Statement::ConditionEval(eval) => eval.span.offset,
+            Statement::IfLadder(ladder) => ladder.blocks[0].start.span.offset,
+            Statement::LoopBlock(block) => block.start.span.offset,
+            Statement::LoopControl(control) => control.span.offset,
+        }
+    }
+}
@@ -1,33 +1,43 @@
-use crate::ast::object::Object;
+use crate::ast::{SrcSpan, object::Object};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct CondEqual {
     pub(crate) a: Object,
-    pub(crate) b: Object
+    pub(crate) b: Object,
+    pub(crate) a_span: SrcSpan,
+    pub(crate) b_span: SrcSpan
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct CondGreater {
     pub(crate) a: Object,
     pub(crate) b: Object,
+    pub(crate) a_span: SrcSpan,
+    pub(crate) b_span: SrcSpan,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct CondLess {
     pub(crate) a: Object,
     pub(crate) b: Object,
+    pub(crate) a_span: SrcSpan,
+    pub(crate) b_span: SrcSpan,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct CondAndEqual {
     pub(crate) ps: Vec<Object>,
-    pub(crate) q: Object
+    pub(crate) q: Object,
+    pub(crate) p_spans: Vec<SrcSpan>,
+    pub(crate) q_span: SrcSpan
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct CondOrEqual {
     pub(crate) ps: Vec<Object>,
-    pub(crate) q: Object
+    pub(crate) q: Object,
+    pub(crate) p_spans: Vec<SrcSpan>,
+    pub(crate) q_span: SrcSpan
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,4 +47,21 @@ pub(crate) enum Condition {
     Less(CondLess),
     AndEqual(CondAndEqual),
     OrEqual(CondOrEqual)
-}
\ No newline at end of file
+}
+
+impl Condition {
+    /// Gets the objects in the condition, in the order they are written, along with their spans.
+    pub(crate) fn objects(&self) -> Vec<(&Object, SrcSpan)> {
+        match self {
+            Condition::Equal(cond) => vec![(&cond.a, cond.a_span), (&cond.b, cond.b_span)],
+            Condition::Greater(cond) => vec![(&cond.a, cond.a_span), (&cond.b, cond.b_span)],
+            Condition::Less(cond) => vec![(&cond.a, cond.a_span), (&cond.b, cond.b_span)],
+            Condition::AndEqual(cond) => cond.ps.iter().zip(cond.p_spans.iter().copied())
+                .chain([(&cond.q, cond.q_span)])
+                .collect(),
+            Condition::OrEqual(cond) => cond.ps.iter().zip(cond.p_spans.iter().copied())
+                .chain([(&cond.q, cond.q_span)])
+                .collect(),
+        }
+    }
+}
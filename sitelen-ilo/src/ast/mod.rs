@@ -1,4 +1,13 @@
 pub(crate) mod condition;
 pub(crate) mod object;
 pub(crate) mod statement;
-pub(crate) mod block;
\ No newline at end of file
+pub(crate) mod block;
+
+/// A region of the source code, kept in the AST so errors can be reported after parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct SrcSpan {
+    /// Offset from the start of the source, in bytes.
+    pub(crate) offset: usize,
+    /// Length of the region, in bytes.
+    pub(crate) len: usize,
+}
@@ -1,7 +1,9 @@
+use std::fmt::Display;
 
+use sitelen_ilo_macros::sp_c;
 
 /// One of the primitive data types (*toki*, *nanpa*, or *lon*).
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum PrimitiveType {
     /// UTF-8 string.
     Toki,
@@ -10,7 +12,21 @@ pub(crate) enum PrimitiveType {
     /// Boolean value.
     Lon,
 }
-
+impl PrimitiveType {
+    /// Gets the word for this type.
+    pub(crate) fn word(self) -> char {
+        match self {
+            PrimitiveType::Toki => sp_c!("toki"),
+            PrimitiveType::Nanpa => sp_c!("nanpa"),
+            PrimitiveType::Lon => sp_c!("lon"),
+        }
+    }
+}
+impl Display for PrimitiveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("['{}']", self.word()))
+    }
+}
 
 /// A value that has a [`PrimitiveType`]
 pub(crate) trait TypedValue {
@@ -36,7 +52,7 @@ impl TypedValue for Literal {
 }
 
 /// A named variable.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct Variable {
     pub(crate) ptype: PrimitiveType,
     pub(crate) name: String,
@@ -64,4 +80,13 @@ pub(crate) enum Object {
     Variable(Variable),
     Literal(Literal),
     Ni(NiRef),
-}
\ No newline at end of file
+}
+impl TypedValue for Object {
+    fn get_type(&self) -> PrimitiveType {
+        match self {
+            Object::Variable(var) => var.get_type(),
+            Object::Literal(lit) => lit.get_type(),
+            Object::Ni(ni) => ni.get_type(),
+        }
+    }
+}
@@ -1,22 +1,52 @@
-use crate::ast::{condition::Condition, object::Object};
+use sitelen_ilo_macros::sp_c;
+
+use crate::ast::{
+    SrcSpan,
+    condition::Condition,
+    object::{Object, Variable},
+};
 
 /// A target for a command statement.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum Target {
+    /// The system, or outside world (*ilo*).
     Ilo,
+    /// A named variable.
+    Variable(Variable),
 }
 
 /// A keyword that begins an argument.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum ArgKey {
     E,
     Tawa,
     Tan,
     Kepeken,
+    Sama,
+}
+impl ArgKey {
+    /// Gets the word for this key.
+    pub(crate) fn word(self) -> char {
+        match self {
+            ArgKey::E => sp_c!("e"),
+            ArgKey::Tawa => sp_c!("tawa"),
+            ArgKey::Tan => sp_c!("tan"),
+            ArgKey::Kepeken => sp_c!("kepeken"),
+            ArgKey::Sama => sp_c!("sama"),
+        }
+    }
 }
 
-/// A keyword that denotes a block.
+/// An argument to a command, preceded by its [`ArgKey`].
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Argument {
+    pub(crate) key: ArgKey,
+    pub(crate) value: Object,
+    pub(crate) span: SrcSpan,
+}
+
+/// A keyword that denotes a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum BlockType {
     /// A run-once block.
     Pali,
@@ -24,18 +54,33 @@ pub(crate) enum BlockType {
     Sike,
 }
 
+/// A comment (started with *len*).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Comment {
-    content: String,
+    pub(crate) content: String,
+    pub(crate) span: SrcSpan,
+}
+
+/// One action of a command (started with *o*), with its arguments.
+///
+/// Assignments (`[variable]󱥄[value]`) have an empty `name` and take the value as their only
+/// argument, under [`ArgKey::E`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Action {
+    pub(crate) name: String,
+    pub(crate) args: Vec<Argument>,
+    pub(crate) span: SrcSpan,
 }
 
 /// A command statement (one which uses *o*, generally performing some action.)
+///
+/// Commands may have several actions on the same target, which run from left to right.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Command {
     pub(crate) condition: Option<Condition>,
     pub(crate) target: Option<Target>,
-    action: String,
-    args: Vec<(ArgKey, Object)>,
+    pub(crate) actions: Vec<Action>,
+    pub(crate) span: SrcSpan,
 }
 
 /// The beginning of a block, potentially with a condition or chain.
@@ -44,10 +89,29 @@ pub(crate) struct BlockStart {
     pub(crate) chained: bool,
     pub(crate) condition: Option<Condition>,
     pub(crate) block_type: BlockType,
+    pub(crate) span: SrcSpan,
+}
+
+/// A keyword that jumps within the innermost loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LoopControlKind {
+    /// Ends the loop (*sike o pini*).
+    Pini,
+    /// Skips to the next iteration (*sike o sin*).
+    Sin,
+}
+
+/// A loop control statement, potentially with a condition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoopControl {
+    pub(crate) condition: Option<Condition>,
+    pub(crate) kind: LoopControlKind,
+    pub(crate) span: SrcSpan,
 }
 
 /// A condition evaluation (using *ken la*).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ConditionEval {
     pub(crate) condition: Condition,
+    pub(crate) span: SrcSpan,
 }
\ No newline at end of file
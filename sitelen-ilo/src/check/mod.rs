@@ -0,0 +1,265 @@
+//! Static checks run over a program before it starts.
+
+use std::collections::HashSet;
+
+use sitelen_ilo_macros::sp;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        condition::Condition,
+        object::{Object, PrimitiveType, TypedValue, Variable},
+        statement::{Action, Command, Target},
+    },
+    parse::error::ParseError,
+    runtime::{
+        ERR_ASSIGN_TYPE, ERR_CHAIN_LON, ERR_COMPARE_NANPA, ERR_COMPARE_TYPES, ERR_REDECLARED,
+        ERR_UNDECLARED, HELP_UNDECLARED,
+    },
+};
+
+mod ni;
+
+pub(crate) use ni::check_ni;
+
+/// Walks a program, keeping track of which variables are declared.
+struct Checker<'a> {
+    /// The source code of the program, used to locate errors.
+    source: &'a str,
+    /// Variables declared in each block, from outermost to innermost.
+    scopes: Vec<HashSet<Variable>>,
+    /// Errors found so far.
+    errors: Vec<ParseError<'a>>,
+}
+impl<'a> Checker<'a> {
+    fn report(&mut self, span: SrcSpan, reason: &str) {
+        self.errors
+            .push(ParseError::new(span.locate(self.source), reason));
+    }
+
+    fn report_undeclared(&mut self, span: SrcSpan) {
+        let err = ParseError::new(span.locate(self.source), ERR_UNDECLARED);
+        self.errors.push(err.with_help(HELP_UNDECLARED));
+    }
+
+    fn is_declared(&self, var: &Variable) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(var))
+    }
+
+    /// Checks statements in a new scope.
+    fn check_block(&mut self, stmts: &[Statement]) {
+        self.scopes.push(HashSet::new());
+        self.check_all(stmts);
+        self.scopes.pop();
+    }
+
+    fn check_all(&mut self, stmts: &[Statement]) {
+        for stmt in stmts {
+            self.check_statement(stmt);
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Comment(_) => (),
+            Statement::Command(cmd) => self.check_command(cmd),
+            Statement::ConditionEval(eval) => self.check_condition(&eval.condition),
+            Statement::IfLadder(ladder) => {
+                for block in &ladder.blocks {
+                    if let Some(cond) = &block.start.condition {
+                        self.check_condition(cond);
+                    }
+                    self.check_block(&block.contents);
+                }
+            }
+            Statement::LoopBlock(block) => {
+                if let Some(cond) = &block.start.condition {
+                    self.check_condition(cond);
+                }
+                self.check_block(&block.contents);
+            }
+            Statement::LoopControl(control) => {
+                if let Some(cond) = &control.condition {
+                    self.check_condition(cond);
+                }
+            }
+        }
+    }
+
+    fn check_command(&mut self, cmd: &Command) {
+        if let Some(cond) = &cmd.condition {
+            self.check_condition(cond);
+        }
+        for action in &cmd.actions {
+            match &cmd.target {
+                Some(Target::Variable(var)) => self.check_variable_action(var, action),
+                _ => self.check_args(action),
+            }
+        }
+    }
+
+    /// Checks an action done on a variable, which may declare it.
+    fn check_variable_action(&mut self, var: &Variable, action: &Action) {
+        if action.name == sp!("sin") {
+            let scope = self
+                .scopes
+                .last_mut()
+                .expect("there should always be a scope");
+            if !scope.insert(var.clone()) {
+                self.report(action.span, ERR_REDECLARED);
+            }
+            return;
+        }
+
+        self.check_args(action);
+        if !self.is_declared(var) {
+            self.report_undeclared(action.span);
+        }
+        // assignment
+        if let ("", [arg]) = (action.name.as_str(), &action.args[..])
+            && arg.value.get_type() != var.ptype
+        {
+            self.report(arg.span, ERR_ASSIGN_TYPE);
+        }
+    }
+
+    fn check_args(&mut self, action: &Action) {
+        for arg in &action.args {
+            self.check_object(&arg.value, arg.span);
+        }
+    }
+
+    fn check_object(&mut self, obj: &Object, span: SrcSpan) {
+        if let Object::Variable(var) = obj
+            && !self.is_declared(var)
+        {
+            self.report_undeclared(span);
+        }
+    }
+
+    /// Checks a condition, reporting mistakes at the objects they are found in.
+    fn check_condition(&mut self, cond: &Condition) {
+        let objects = cond.objects();
+        let (expected, reason) = match cond {
+            // the first object decides the type of the others
+            Condition::Equal(cond) => (cond.a.get_type(), ERR_COMPARE_TYPES),
+            Condition::Greater(_) | Condition::Less(_) => (PrimitiveType::Nanpa, ERR_COMPARE_NANPA),
+            Condition::AndEqual(_) | Condition::OrEqual(_) => (PrimitiveType::Lon, ERR_CHAIN_LON),
+        };
+
+        if let Some((_, span)) = objects.iter().find(|(obj, _)| obj.get_type() != expected) {
+            self.report(*span, reason);
+        }
+        for (obj, span) in objects {
+            self.check_object(obj, span);
+        }
+    }
+}
+
+/// Checks a program for errors that can be found without running it, such as variables being
+/// used before they are declared, or comparisons between the wrong types.
+pub(crate) fn check_program<'a>(source: &'a str, program: &[Statement]) -> Vec<ParseError<'a>> {
+    let mut checker = Checker {
+        source,
+        scopes: vec![HashSet::new()],
+        errors: Vec::new(),
+    };
+    checker.check_all(program);
+    checker.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{check::check_program, parse::parse_program};
+
+    fn check_valid(lines: &[&str]) {
+        let program = lines.join("\n");
+        let stmts = parse_program(&program).expect("program should parse");
+        let errors = check_program(&program, &stmts);
+        assert!(errors.is_empty(), "unexpected errors {:?}", errors);
+    }
+    fn check_invalid(lines: &[&str], err_fragments: &[&str]) {
+        let program = lines.join("\n");
+        let stmts = parse_program(&program).expect("program should parse");
+        let errors = check_program(&program, &stmts);
+        let fragments: Vec<_> = errors.iter().map(|err| *err.span().fragment()).collect();
+        assert_eq!(fragments, err_fragments);
+    }
+
+    #[test]
+    fn test_example() {
+        let program = include_str!("../../../examples/musi_nanpa.lipu");
+        let stmts = parse_program(program).expect("example should parse");
+        assert!(check_program(program, &stmts).is_empty());
+    }
+
+    #[test]
+    fn test_scopes() {
+        check_valid(&[
+            sp!("nanpa [ijo] o sin"),
+            sp!("nanpa [ijo] li lili tawa nanpa <luka> la o sike"),
+            sp!("nanpa [jan] o sin o nanpa [ijo]"),
+            sp!("o wan e nanpa [jan] e nanpa <wan>"),
+            sp!("nanpa [ijo] o nanpa ni"),
+            sp!("pini"),
+            sp!("o pali"),
+            sp!("nanpa [jan] o sin"),
+            sp!("pini"),
+        ]);
+        check_invalid(
+            &[
+                sp!("o pali"),
+                sp!("nanpa [ijo] o sin"),
+                sp!("pini"),
+                sp!("ilo o toki e nanpa [ijo]"),
+            ],
+            &[sp!("e nanpa [ijo]")],
+        );
+        check_invalid(
+            &[
+                sp!("toki [ijo] o sin"),
+                sp!("o pali"),
+                sp!("toki [ijo] o sin"),
+                sp!("pini"),
+                sp!("toki [ijo] o sin"),
+            ],
+            &[sp!("o sin")],
+        );
+        check_invalid(&[sp!("lon [ijo] o lon <lon> o sin")], &[sp!("o lon <lon>")]);
+    }
+
+    #[test]
+    fn test_types() {
+        check_invalid(
+            &[sp!("nanpa [ijo] o sin"), sp!("nanpa [ijo] o toki <wan>")],
+            &[sp!("toki <wan>")],
+        );
+        check_invalid(
+            &[sp!("ken la nanpa <wan> li toki <wan>")],
+            &[sp!("toki <wan>")],
+        );
+        check_invalid(
+            &[
+                sp!("toki <wan> li suli tawa nanpa <wan> la o pali"),
+                sp!("pini"),
+            ],
+            &[sp!("toki <wan>")],
+        );
+        check_invalid(
+            &[
+                sp!("o sike"),
+                sp!("lon <lon> en nanpa ni li lon <lon> la sike o pini"),
+                sp!("pini"),
+            ],
+            &[sp!("nanpa ni")],
+        );
+        // mistakes in a condition are reported at the object, not the whole statement
+        check_invalid(
+            &[sp!("toki [a] li suli tawa nanpa <wan> la ilo o toki e toki [a]")],
+            &[sp!("toki [a]"), sp!("toki [a]"), sp!("e toki [a]")],
+        );
+    }
+}
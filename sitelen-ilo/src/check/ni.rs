@@ -0,0 +1,311 @@
+//! Flow analysis of the types *ni* can hold.
+
+use sitelen_ilo_macros::sp;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        condition::Condition,
+        object::{Object, PrimitiveType},
+        statement::{Action, LoopControlKind, Target},
+    },
+    parse::error::ParseError,
+    runtime::intrinsic,
+};
+
+const HELP_NI_TYPES: &str = "at this point, ni may hold";
+
+/// The types *ni* might hold at some point in a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct NiTypes(u8);
+impl NiTypes {
+    /// Nothing; this point can't be reached.
+    const UNREACHABLE: Self = Self(0);
+    /// *ni* has not been given a value yet.
+    const EMPTY: Self = Self(1);
+    /// *ni* holds a value of any type.
+    const ANY: Self = Self(0b1110);
+
+    const fn of(ptype: PrimitiveType) -> Self {
+        match ptype {
+            PrimitiveType::Toki => Self(0b0010),
+            PrimitiveType::Nanpa => Self(0b0100),
+            PrimitiveType::Lon => Self(0b1000),
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Replaces the types, unless this point can't be reached.
+    fn then(self, next: Self) -> Self {
+        match self {
+            Self::UNREACHABLE => Self::UNREACHABLE,
+            _ => next,
+        }
+    }
+
+    /// Lists the types, for help messages.
+    fn describe(self) -> String {
+        let types = [
+            PrimitiveType::Toki,
+            PrimitiveType::Nanpa,
+            PrimitiveType::Lon,
+        ];
+        let mut parts: Vec<String> = types
+            .into_iter()
+            .filter(|&ptype| self.contains(Self::of(ptype)))
+            .map(|ptype| ptype.to_string())
+            .collect();
+        if self.contains(Self::EMPTY) {
+            parts.push("no value".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Where control leaves a loop early.
+#[derive(Default)]
+struct LoopExits {
+    /// Types of *ni* at each *sike o sin*.
+    sin: NiTypes,
+    /// Types of *ni* at each *sike o pini*.
+    pini: NiTypes,
+}
+
+/// Walks a program, following the types of *ni* through it.
+struct NiChecker<'a> {
+    /// The source code of the program, used to locate problems.
+    source: &'a str,
+    /// Whether problems should be reported. This is off while looking for a loop's fixed point.
+    report: bool,
+    /// Exits of each loop, from outermost to innermost.
+    loops: Vec<LoopExits>,
+    /// Problems found so far.
+    problems: Vec<ParseError<'a>>,
+}
+impl<'a> NiChecker<'a> {
+    /// Checks an object that is read when *ni* may hold `state`.
+    fn read(&mut self, obj: &Object, span: SrcSpan, state: NiTypes) {
+        let Object::Ni(ni) = obj else {
+            return;
+        };
+        let wanted = NiTypes::of(ni.ptype);
+        let reason = if !self.report || state == NiTypes::UNREACHABLE || state == wanted {
+            return;
+        } else if state.contains(wanted) {
+            format!("ni might not hold a {} value here", ni.ptype)
+        } else {
+            format!("ni can never hold a {} value here", ni.ptype)
+        };
+
+        let help = format!("{} {}", HELP_NI_TYPES, state.describe());
+        let err = ParseError::new(span.locate(self.source), reason).with_help(help);
+        self.problems.push(err);
+    }
+
+    fn read_condition(&mut self, cond: Option<&Condition>, state: NiTypes) {
+        let Some(cond) = cond else {
+            return;
+        };
+        for (obj, span) in cond.objects() {
+            self.read(obj, span, state);
+        }
+    }
+
+    fn flow_all(&mut self, stmts: &[Statement], mut state: NiTypes) -> NiTypes {
+        for stmt in stmts {
+            state = self.flow(stmt, state);
+        }
+        state
+    }
+
+    /// Finds the types *ni* may hold after a statement.
+    fn flow(&mut self, stmt: &Statement, state: NiTypes) -> NiTypes {
+        match stmt {
+            Statement::Comment(_) => state,
+            Statement::Command(cmd) => {
+                self.read_condition(cmd.condition.as_ref(), state);
+                let mut after = state;
+                for action in &cmd.actions {
+                    after = self.flow_action(&cmd.target, action, after);
+                }
+                match cmd.condition {
+                    Some(_) => after.union(state),
+                    None => after,
+                }
+            }
+            Statement::ConditionEval(eval) => {
+                self.read_condition(Some(&eval.condition), state);
+                state.then(NiTypes::of(PrimitiveType::Lon))
+            }
+            Statement::IfLadder(ladder) => {
+                let mut after = NiTypes::UNREACHABLE;
+                for block in &ladder.blocks {
+                    self.read_condition(block.start.condition.as_ref(), state);
+                    after = after.union(self.flow_all(&block.contents, state));
+                }
+                // without an else block, none of the blocks might run
+                match ladder.blocks.last() {
+                    Some(block) if block.start.condition.is_none() => after,
+                    _ => after.union(state),
+                }
+            }
+            Statement::LoopBlock(block) => {
+                // find what ni may hold at the top of the loop
+                let report = std::mem::replace(&mut self.report, false);
+                let mut head = state;
+                loop {
+                    self.loops.push(LoopExits::default());
+                    let end = self.flow_all(&block.contents, head);
+                    let exits = self.loops.pop().expect("loop was just pushed");
+                    let next = head.union(end).union(exits.sin);
+                    if next == head {
+                        break;
+                    }
+                    head = next;
+                }
+                self.report = report;
+
+                self.read_condition(block.start.condition.as_ref(), head);
+                self.loops.push(LoopExits::default());
+                self.flow_all(&block.contents, head);
+                let exits = self.loops.pop().expect("loop was just pushed");
+                match block.start.condition {
+                    Some(_) => exits.pini.union(head),
+                    None => exits.pini,
+                }
+            }
+            Statement::LoopControl(control) => {
+                self.read_condition(control.condition.as_ref(), state);
+                if let Some(exits) = self.loops.last_mut() {
+                    match control.kind {
+                        LoopControlKind::Pini => exits.pini = exits.pini.union(state),
+                        LoopControlKind::Sin => exits.sin = exits.sin.union(state),
+                    }
+                }
+                match control.condition {
+                    Some(_) => state,
+                    None => NiTypes::UNREACHABLE,
+                }
+            }
+        }
+    }
+
+    /// Finds the types *ni* may hold after an action.
+    fn flow_action(&mut self, target: &Option<Target>, action: &Action, state: NiTypes) -> NiTypes {
+        for arg in &action.args {
+            self.read(&arg.value, arg.span, state);
+        }
+        match target {
+            None => state.then(
+                intrinsic::result_type(&action.name, &action.args)
+                    .map_or(NiTypes::ANY, NiTypes::of),
+            ),
+            Some(Target::Ilo) if action.name == sp!("wile linja") => {
+                state.then(NiTypes::of(PrimitiveType::Toki))
+            }
+            Some(_) => state,
+        }
+    }
+}
+
+/// Finds reads of *ni* that might not match the type *ni* holds at that point.
+pub(crate) fn check_ni<'a>(source: &'a str, program: &[Statement]) -> Vec<ParseError<'a>> {
+    let mut checker = NiChecker {
+        source,
+        report: true,
+        loops: Vec::new(),
+        problems: Vec::new(),
+    };
+    checker.flow_all(program, NiTypes::EMPTY);
+    checker.problems
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{check::ni::check_ni, parse::parse_program};
+
+    fn check(lines: &[&str]) -> Vec<String> {
+        let program = lines.join("\n");
+        let stmts = parse_program(&program).expect("program should parse");
+        check_ni(&program, &stmts)
+            .iter()
+            .map(|err| err.span().fragment().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let program = include_str!("../../../examples/musi_nanpa.lipu");
+        let stmts = parse_program(program).expect("example should parse");
+        assert!(check_ni(program, &stmts).is_empty());
+    }
+
+    #[test]
+    fn test_straight() {
+        let problems = check(&[
+            sp!("ilo o toki e toki ni"),
+            sp!("o wan linja e toki <jan> e toki <pona>"),
+            sp!("ilo o toki e toki ni e nanpa ni"),
+            sp!("ken la nanpa <wan> li nanpa <tu>"),
+            sp!("lon ni li lon <lon> la ilo o wile linja"),
+            sp!("ilo o toki e toki ni"),
+        ]);
+        assert_eq!(
+            problems,
+            [sp!("e toki ni"), sp!("e nanpa ni"), sp!("e toki ni"),]
+        );
+    }
+
+    #[test]
+    fn test_ladder() {
+        let problems = check(&[
+            sp!("o wan e nanpa <wan>"),
+            sp!("nanpa ni li nanpa <wan> la o pali"),
+            sp!("o wan linja e toki <jan>"),
+            sp!("ala la o pali"),
+            sp!("o wan e nanpa <tu>"),
+            sp!("pini"),
+            sp!("ilo o toki e nanpa ni"),
+            sp!("o wan linja e toki <jan>"),
+            sp!("o pali"),
+            sp!("o ante toki e toki ni"),
+            sp!("pini"),
+            sp!("ilo o toki e toki ni"),
+        ]);
+        assert_eq!(problems, [sp!("e nanpa ni")]);
+    }
+
+    #[test]
+    fn test_loop() {
+        let problems = check(&[
+            sp!("o wan e nanpa <wan>"),
+            sp!("o sike"),
+            sp!("o wan e nanpa ni e nanpa <wan>"),
+            sp!("nanpa ni li suli tawa nanpa <luka> la sike o pini"),
+            sp!("o ante toki e nanpa ni"),
+            sp!("pini"),
+            sp!("ilo o toki e nanpa ni"),
+        ]);
+        assert_eq!(problems, [sp!("e nanpa ni")]);
+
+        let problems = check(&[
+            sp!("o wan e nanpa <wan>"),
+            sp!("o sike"),
+            sp!("ken la nanpa ni li nanpa <tu>"),
+            sp!("lon ni li lon <lon> la sike o pini"),
+            sp!("pini"),
+            sp!("ilo o toki e lon ni"),
+        ]);
+        assert_eq!(problems, [sp!("nanpa ni")]);
+    }
+}
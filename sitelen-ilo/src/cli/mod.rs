@@ -3,10 +3,118 @@ use std::sync::OnceLock;
 #[derive(clap::Parser)]
 #[command(name = "o-ilo")]
 #[command(about = "Runs a sitelen ilo program.")]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
-    pub input: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[arg(help = "The program to run. If left out, starts an interactive session.")]
+    pub input: Option<String>,
     #[arg(long = "log", help = "Sets the log level.", default_value_t = log::LevelFilter::Error)]
     pub log_level: log::LevelFilter,
+    #[arg(long, help = "Treats warnings about ni as errors.")]
+    pub strict: bool,
+    #[arg(
+        long,
+        help = "Reads the program as sitelen Lasina. This is the default for .lipu.txt files."
+    )]
+    pub lasina: bool,
+    #[arg(long, value_enum, default_value_t = Backend::Tree, help = "Sets how the program is run.")]
+    pub backend: Backend,
+    #[arg(short = 'O', long, help = "Optimises the program before running it.")]
+    pub optimize: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "Prints the program at a stage of compiling, instead of running it."
+    )]
+    pub emit: Option<Emit>,
+}
+
+/// Ways of running a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Walks the syntax tree directly.
+    Tree,
+    /// Compiles the program to bytecode, and runs it on a virtual machine.
+    Vm,
+}
+
+/// Stages of compiling a program that can be printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Emit {
+    /// The program after optimising, formatted as source code. The program is optimised even
+    /// without `--optimize`.
+    OptimizedAst,
+}
+
+/// Tools other than running a program.
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Prints a program in sitelen Lasina, for reading without a sitelen pona font.
+    Lasina {
+        #[arg(help = "The program to print.")]
+        input: String,
+    },
+    /// Formats a program in place, with canonical indentation and spelling.
+    Fmt {
+        #[arg(help = "The program to format.")]
+        input: String,
+        #[arg(
+            long,
+            help = "Exits with an error if the program isn't formatted, without changing it."
+        )]
+        check: bool,
+        #[arg(
+            long,
+            help = "Reads the program as sitelen Lasina. This is the default for .lipu.txt files."
+        )]
+        lasina: bool,
+    },
+    /// Prints a program with its syntax highlighted.
+    Highlight {
+        #[arg(help = "The program to highlight.")]
+        input: String,
+        #[arg(long, value_enum, default_value_t = HighlightFormat::Html, help = "The output format.")]
+        format: HighlightFormat,
+    },
+    /// Compiles a program to another language.
+    Build {
+        #[arg(help = "The program to compile.")]
+        input: String,
+        #[arg(long, value_enum, default_value_t = BuildTarget::C, help = "The language to compile to.")]
+        target: BuildTarget,
+        #[arg(
+            short,
+            long,
+            help = "Where to write the output. Defaults to the input with its extension replaced."
+        )]
+        output: Option<String>,
+        #[arg(
+            long,
+            help = "Reads the program as sitelen Lasina. This is the default for .lipu.txt files."
+        )]
+        lasina: bool,
+        #[arg(short = 'O', long, help = "Optimises the program before compiling it.")]
+        optimize: bool,
+    },
+    /// Runs a language server for editors, speaking the Language Server Protocol over stdio.
+    Lsp,
+}
+
+/// Languages that programs can be compiled to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BuildTarget {
+    /// A single C99 file, with no dependencies other than the standard library.
+    C,
+}
+
+/// Output formats for highlighted programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HighlightFormat {
+    /// HTML, with a CSS class on each token.
+    Html,
+    /// A JSON array of tokens, for editor plugins.
+    Json,
 }
 
 impl Cli {
@@ -0,0 +1,322 @@
+//! Rendering of errors and warnings for the terminal.
+
+use std::fmt::Write;
+
+use sitelen_ilo_lasina::Transliteration;
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    ast::SrcSpan,
+    parse::{Span, error::ParseError},
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const CYAN: &str = "\x1b[1;36m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// How many columns a tab is shown as.
+const TAB_WIDTH: usize = 4;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn colour(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+/// Number of columns a character takes up in a terminal.
+///
+/// Sitelen pona glyphs are drawn twice as wide as Latin letters by the fonts that support them, but
+/// they live in a private use area, so Unicode doesn't know that.
+fn char_width(ch: char) -> usize {
+    match ch {
+        '\t' => TAB_WIDTH,
+        '\u{F1900}'..='\u{F19FF}' => 2,
+        _ => ch.width().unwrap_or(0),
+    }
+}
+
+fn str_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Renders [`ParseError`]s along with the source line they point at.
+pub(crate) struct Renderer<'a> {
+    /// Name of the file, shown before the line and column.
+    path: &'a str,
+    /// The whole source file.
+    source: &'a str,
+    /// Whether to use ANSI colours.
+    colour: bool,
+    /// If the source was written in sitelen Lasina, how it was transliterated.
+    transliteration: Option<&'a Transliteration>,
+}
+impl<'a> Renderer<'a> {
+    pub(crate) fn new(path: &'a str, source: &'a str, colour: bool) -> Self {
+        Self {
+            path,
+            source,
+            colour,
+            transliteration: None,
+        }
+    }
+
+    /// Renders errors from a transliterated version of the source, pointing them back at the
+    /// original text.
+    pub(crate) fn with_transliteration(mut self, transliteration: &'a Transliteration) -> Self {
+        self.transliteration = Some(transliteration);
+        self
+    }
+
+    /// Wraps text in an ANSI style, if colours are on.
+    fn paint(&self, style: &str, text: &str) -> String {
+        match self.colour {
+            true => format!("{}{}{}", style, text, RESET),
+            false => text.to_string(),
+        }
+    }
+
+    /// Renders an error, with each error that caused it as a note.
+    pub(crate) fn render(&self, severity: Severity, err: &ParseError) -> String {
+        let Some(transliteration) = self.transliteration else {
+            return self.render_located(severity, err);
+        };
+        let err = err.relocate(&|span: Span| {
+            let start = span.location_offset();
+            let range = transliteration.source_range(start..start + span.fragment().len());
+            SrcSpan {
+                offset: range.start,
+                len: range.len(),
+            }
+            .locate(self.source)
+        });
+        self.render_located(severity, &err)
+    }
+
+    /// Renders an error whose spans point into the source.
+    fn render_located(&self, severity: Severity, err: &ParseError) -> String {
+        let mut out = String::new();
+        let gutter = self.gutter_width(err);
+
+        let label = self.paint(severity.colour(), severity.label());
+        let reason = self.paint(BOLD, err.reason());
+        writeln!(out, "{}: {}", label, reason).unwrap();
+        self.render_snippet(&mut out, err, severity.colour(), gutter);
+
+        let mut cause = err.cause();
+        while let Some(inner) = cause {
+            let label = self.paint(GREEN, "note");
+            writeln!(out, "{}: caused by: {}", label, inner.reason()).unwrap();
+            self.render_snippet(&mut out, inner, GREEN, gutter);
+            cause = inner.cause();
+        }
+        out
+    }
+
+    /// Renders a final line counting the errors that were found.
+    pub(crate) fn render_count(&self, severity: Severity, count: usize, what: &str) -> String {
+        let label = self.paint(severity.colour(), severity.label());
+        let plural = if count == 1 { "" } else { "s" };
+        let summary = format!("found {} {}{}", count, what, plural);
+        format!("{}: {}\n", label, self.paint(BOLD, &summary))
+    }
+
+    /// Width of the line number column, wide enough for every error in the chain.
+    fn gutter_width(&self, err: &ParseError) -> usize {
+        let mut line = 0;
+        let mut err = Some(err);
+        while let Some(inner) = err {
+            line = line.max(inner.span().location_line());
+            err = inner.cause();
+        }
+        line.to_string().len()
+    }
+
+    /// Renders the location of an error, the line it is on, and a caret under the error.
+    fn render_snippet(&self, out: &mut String, err: &ParseError, colour: &str, gutter: usize) {
+        let span = err.span();
+        let line_no = span.location_line();
+        let pad = " ".repeat(gutter);
+        let bar = self.paint(BLUE, "|");
+
+        writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            pad,
+            self.paint(BLUE, "-->"),
+            self.path,
+            line_no,
+            span.get_utf8_column()
+        )
+        .unwrap();
+
+        let (before, marked, after) = self.split_line(span);
+        let text: String = format!("{}{}{}", before, marked, after)
+            .chars()
+            .flat_map(|ch| match ch {
+                '\t' => vec![' '; TAB_WIDTH],
+                ch => vec![ch],
+            })
+            .collect();
+        let caret = format!(
+            "{}{}",
+            " ".repeat(str_width(before)),
+            "^".repeat(str_width(marked).max(1))
+        );
+
+        writeln!(out, "{} {}", pad, bar).unwrap();
+        writeln!(
+            out,
+            "{} {} {}",
+            self.paint(BLUE, &format!("{:>gutter$}", line_no)),
+            bar,
+            text
+        )
+        .unwrap();
+        writeln!(out, "{} {} {}", pad, bar, self.paint(colour, &caret)).unwrap();
+        if let Some(help) = err.help() {
+            writeln!(
+                out,
+                "{} {} {}: {}",
+                pad,
+                self.paint(BLUE, "="),
+                self.paint(CYAN, "help"),
+                help
+            )
+            .unwrap();
+        }
+    }
+
+    /// Splits the line a span starts on into the text before the span, the part of the span on
+    /// that line, and the text after it.
+    fn split_line(&self, span: Span) -> (&'a str, &'a str, &'a str) {
+        let start = span.location_offset().min(self.source.len());
+        let line_start = self.source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.source[start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| start + i);
+        let end = (start + span.fragment().len()).min(line_end);
+
+        let after = &self.source[end..line_end];
+        (
+            &self.source[line_start..start],
+            &self.source[start..end],
+            after.strip_suffix('\r').unwrap_or(after),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::SrcSpan,
+        diagnostic::{Renderer, Severity, str_width},
+        parse::error::ParseError,
+    };
+
+    #[test]
+    fn test_width() {
+        assert_eq!(str_width("jan"), 3);
+        assert_eq!(str_width(sp!("jan pona")), 4);
+        assert_eq!(str_width(sp!("toki <jan>")), 8);
+        assert_eq!(str_width("\tjan"), 7);
+    }
+
+    #[test]
+    fn test_render() {
+        let source = [sp!("nanpa [ijo] o sin"), sp!("ilo o toki e nanpa [ijo]")].join("\n");
+        let offset = source.find(sp!("e nanpa")).unwrap();
+        let span = SrcSpan {
+            offset,
+            len: sp!("e nanpa").len(),
+        }
+        .locate(&source);
+        let err = ParseError::new(span, "inner").with_help("help");
+        let err = err.chain_up(span, "outer");
+
+        let renderer = Renderer::new("a.lipu", &source, false);
+        let lines = [
+            "error: outer".to_string(),
+            " --> a.lipu:2:4".to_string(),
+            "  |".to_string(),
+            format!("2 | {}", sp!("ilo o toki e nanpa [ijo]")),
+            "  |       ^^^^".to_string(),
+            "note: caused by: inner".to_string(),
+            " --> a.lipu:2:4".to_string(),
+            "  |".to_string(),
+            format!("2 | {}", sp!("ilo o toki e nanpa [ijo]")),
+            "  |       ^^^^".to_string(),
+            "  = help: help".to_string(),
+        ];
+        assert_eq!(
+            renderer.render(Severity::Error, &err),
+            lines.join("\n") + "\n"
+        );
+    }
+
+    #[test]
+    fn test_transliteration() {
+        let source = "o pali\n  ilo o toki e toki <hello>";
+        let transliteration = sitelen_ilo_lasina::source_to_ucsur(source).unwrap();
+        let code = &transliteration.text;
+        let offset = code.find(sp!("e toki")).unwrap();
+        let span = SrcSpan {
+            offset,
+            len: sp!("e toki").len(),
+        }
+        .locate(code);
+        let err = ParseError::new(span, "bad");
+
+        let renderer = Renderer::new("a.lipu.txt", source, false);
+        let rendered = renderer
+            .with_transliteration(&transliteration)
+            .render(Severity::Error, &err);
+        let lines = [
+            "error: bad",
+            " --> a.lipu.txt:2:14",
+            "  |",
+            "2 |   ilo o toki e toki <hello>",
+            "  |              ^^^^^^",
+            "",
+        ];
+        assert_eq!(rendered, lines.join("\n"));
+    }
+
+    #[test]
+    fn test_colour() {
+        let source = sp!("o pali");
+        let span = SrcSpan {
+            offset: 0,
+            len: source.len(),
+        }
+        .locate(source);
+        let err = ParseError::new(span, "bad");
+
+        let plain = Renderer::new("a.lipu", source, false).render(Severity::Warning, &err);
+        assert!(plain.starts_with("warning: bad\n"));
+        assert!(!plain.contains('\x1b'));
+        let colour = Renderer::new("a.lipu", source, true).render(Severity::Warning, &err);
+        assert!(colour.starts_with("\x1b[1;33mwarning\x1b[0m: \x1b[1mbad\x1b[0m\n"));
+    }
+}
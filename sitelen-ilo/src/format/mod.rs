@@ -0,0 +1,327 @@
+//! Canonical formatting of programs.
+
+use sitelen_ilo_macros::{sp, sp_c};
+
+use crate::{
+    ast::{
+        block::Statement,
+        condition::Condition,
+        object::{Literal, Object, TypedValue},
+        statement::{Action, BlockStart, BlockType, LoopControlKind, Target},
+    },
+    parse::object::nanpa_to_text,
+};
+
+/// Indentation for each level of blocks.
+const INDENT: &str = "    ";
+/// Separates *len* from the text of a comment.
+const COMMENT_SPACE: char = '\u{3000}';
+
+fn write_object(out: &mut String, obj: &Object) {
+    match obj {
+        Object::Variable(var) => {
+            out.push(var.ptype.word());
+            out.push(sp_c!("["));
+            out.push_str(&var.name);
+            out.push(sp_c!("]"));
+        }
+        Object::Ni(ni) => {
+            out.push(ni.ptype.word());
+            out.push(sp_c!("ni"));
+        }
+        Object::Literal(lit) => {
+            out.push(obj.get_type().word());
+            out.push(sp_c!("<"));
+            match lit {
+                // quotes are escaped by doubling them up
+                Literal::Toki(text) => {
+                    for c in text.chars() {
+                        if matches!(c, sp_c!("<") | sp_c!(">")) {
+                            out.push(c);
+                        }
+                        out.push(c);
+                    }
+                }
+                Literal::Nanpa(value) => out.push_str(&nanpa_to_text(*value)),
+                Literal::Lon(true) => out.push(sp_c!("lon")),
+                Literal::Lon(false) => out.push(sp_c!("ala")),
+            }
+            out.push(sp_c!(">"));
+        }
+    }
+}
+
+fn write_condition(out: &mut String, cond: &Condition) {
+    let (ps, joiner, comparison, q) = match cond {
+        Condition::Equal(cond) => (std::slice::from_ref(&cond.a), "", "", &cond.b),
+        Condition::Greater(cond) => (std::slice::from_ref(&cond.a), "", sp!("suli tawa"), &cond.b),
+        Condition::Less(cond) => (std::slice::from_ref(&cond.a), "", sp!("lili tawa"), &cond.b),
+        Condition::AndEqual(cond) => (&cond.ps[..], sp!("en"), "", &cond.q),
+        Condition::OrEqual(cond) => (&cond.ps[..], sp!("anu"), "", &cond.q),
+    };
+    for (i, p) in ps.iter().enumerate() {
+        if i > 0 {
+            out.push_str(joiner);
+        }
+        write_object(out, p);
+    }
+    out.push(sp_c!("li"));
+    out.push_str(comparison);
+    write_object(out, q);
+}
+
+/// Writes a condition followed by *la*, if there is one.
+fn write_condition_la(out: &mut String, cond: &Option<Condition>) {
+    if let Some(cond) = cond {
+        write_condition(out, cond);
+        out.push(sp_c!("la"));
+    }
+}
+
+fn write_action(out: &mut String, action: &Action) {
+    out.push(sp_c!("o"));
+    let mut args = &action.args[..];
+    match (action.name.as_str(), args) {
+        ("", [arg, rest @ ..]) => {
+            write_object(out, &arg.value);
+            args = rest;
+        }
+        (name, _) => out.push_str(name),
+    }
+    for arg in args {
+        out.push(arg.key.word());
+        write_object(out, &arg.value);
+    }
+}
+
+fn write_block_start(out: &mut String, start: &BlockStart) {
+    if start.chained {
+        out.push_str(sp!("ala la"));
+    }
+    write_condition_la(out, &start.condition);
+    out.push(sp_c!("o"));
+    out.push(match start.block_type {
+        BlockType::Pali => sp_c!("pali"),
+        BlockType::Sike => sp_c!("sike"),
+    });
+}
+
+/// Writes formatted statements, one per line.
+struct Formatter<'a> {
+    /// The source code of the program, used to find blank lines.
+    source: &'a str,
+    /// The formatted program so far.
+    out: String,
+    /// How many blocks deep the current line is.
+    depth: usize,
+}
+impl<'a> Formatter<'a> {
+    /// Checks if there is a blank line right before the line containing `offset`.
+    fn blank_before(&self, offset: usize) -> bool {
+        let before = &self.source[..offset];
+        let Some(line_start) = before.rfind('\n') else {
+            return false;
+        };
+        let prev = &before[..line_start];
+        let prev_line = &prev[prev.rfind('\n').map_or(0, |i| i + 1)..];
+        prev_line.trim().is_empty()
+    }
+
+    /// Starts a new line, returning the buffer to write it into.
+    fn line(&mut self) -> &mut String {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        &mut self.out
+    }
+
+    fn end_line(&mut self) {
+        self.out.push('\n');
+    }
+
+    fn write_all(&mut self, stmts: &[Statement]) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            // keep single blank lines between statements
+            if i > 0 && self.blank_before(stmt.offset()) {
+                self.end_line();
+            }
+            self.write(stmt);
+        }
+    }
+
+    fn write_block(&mut self, start: &BlockStart, contents: &[Statement]) {
+        write_block_start(self.line(), start);
+        self.end_line();
+        self.depth += 1;
+        self.write_all(contents);
+        self.depth -= 1;
+    }
+
+    fn write(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Comment(comment) => {
+                let out = self.line();
+                out.push(sp_c!("len"));
+                if !comment.content.is_empty() {
+                    out.push(COMMENT_SPACE);
+                    out.push_str(&comment.content);
+                }
+            }
+            Statement::Command(cmd) => {
+                let out = self.line();
+                write_condition_la(out, &cmd.condition);
+                match &cmd.target {
+                    None => (),
+                    Some(Target::Ilo) => out.push(sp_c!("ilo")),
+                    Some(Target::Variable(var)) => {
+                        write_object(out, &Object::Variable(var.clone()))
+                    }
+                }
+                for action in &cmd.actions {
+                    write_action(out, action);
+                }
+            }
+            Statement::ConditionEval(eval) => {
+                let out = self.line();
+                out.push_str(sp!("ken la"));
+                write_condition(out, &eval.condition);
+            }
+            Statement::IfLadder(ladder) => {
+                for block in &ladder.blocks {
+                    self.write_block(&block.start, &block.contents);
+                }
+                self.line().push(sp_c!("pini"));
+            }
+            Statement::LoopBlock(block) => {
+                self.write_block(&block.start, &block.contents);
+                self.line().push(sp_c!("pini"));
+            }
+            Statement::LoopControl(control) => {
+                let out = self.line();
+                write_condition_la(out, &control.condition);
+                out.push_str(match control.kind {
+                    LoopControlKind::Pini => sp!("sike o pini"),
+                    LoopControlKind::Sin => sp!("sike o sin"),
+                });
+            }
+        }
+        self.end_line();
+    }
+}
+
+/// Formats a program canonically. `source` is the text it was parsed from, used to keep its
+/// `#!` line and blank lines.
+pub(crate) fn format_program(source: &str, program: &[Statement]) -> String {
+    let mut formatter = Formatter {
+        source,
+        out: String::new(),
+        depth: 0,
+    };
+    if source.starts_with("#!") {
+        let shebang = source.lines().next().unwrap_or_default();
+        formatter.out.push_str(shebang.trim_end());
+        formatter.end_line();
+    }
+    formatter.write_all(program);
+    formatter.out
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        format::format_program,
+        parse::parse_program,
+        runtime::{Runtime, io::Streams},
+    };
+
+    fn format(source: &str) -> String {
+        let program = parse_program(source).expect("program should parse");
+        format_program(source, &program)
+    }
+
+    #[test]
+    fn test_example() {
+        let source = include_str!("../../../examples/musi_nanpa.lipu");
+        let formatted = format(source);
+        assert_eq!(format(&formatted), formatted);
+        // the example is already formatted
+        assert_eq!(formatted, source);
+    }
+
+    #[test]
+    fn test_format() {
+        let source = [
+            "#!/usr/bin/env o-ilo ",
+            sp!("len"),
+            sp!("nanpa [ijo] o sin o nanpa <wan wan>"),
+            "",
+            "",
+            sp!("  nanpa [ijo] li suli tawa nanpa <ala> la o sike"),
+            sp!("ken la lon <lon> en lon ni li lon <ala>"),
+            concat!("\t", sp!("len"), "  toki"),
+            sp!("lon ni li lon <lon> la o pali"),
+            sp!("sike o pini"),
+            sp!("ala la o pali"),
+            "",
+            sp!("ilo o toki e toki <<<jan>>> o pini linja"),
+            sp!("pini"),
+            sp!("pini"),
+        ]
+        .join("\n");
+        let expected = [
+            "#!/usr/bin/env o-ilo",
+            sp!("len"),
+            sp!("nanpa [ijo] o sin o nanpa <tu>"),
+            "",
+            sp!("nanpa [ijo] li suli tawa nanpa <ala> la o sike"),
+            &("    ".to_string() + sp!("ken la lon <lon> en lon ni li lon <ala>")),
+            &("    ".to_string() + sp!("len") + "\u{3000}toki"),
+            &("    ".to_string() + sp!("lon ni li lon <lon> la o pali")),
+            &("        ".to_string() + sp!("sike o pini")),
+            &("    ".to_string() + sp!("ala la o pali")),
+            &("        ".to_string() + sp!("ilo o toki e toki <<<jan>>> o pini linja")),
+            &("    ".to_string() + sp!("pini")),
+            sp!("pini"),
+            "",
+        ]
+        .join("\n");
+        assert_eq!(format(&source), expected);
+    }
+
+    #[test]
+    fn test_lasina() {
+        // formatting a program in sitelen Lasina doesn't change what it does
+        let source = concat!(
+            "len  comments are kept: <jan>\n",
+            "  ilo o toki e toki <\u{F1911} x> e toki <jan pona>\n",
+            "ilo o toki e toki <a>>b<<c> e nanpa <luka tu>\n",
+        );
+        let run = |source: &str| {
+            let code = sitelen_ilo_lasina::source_to_ucsur(source).expect("program should load");
+            let program = parse_program(&code.text).expect("program should parse");
+            let mut output = Vec::new();
+            let streams = Streams {
+                input: "".as_bytes(),
+                output: &mut output,
+            };
+            Runtime::with_console(streams)
+                .run(&program)
+                .expect("program should run");
+            (format_program(&code.text, &program), output)
+        };
+
+        let (formatted, output) = run(source);
+        let formatted = sitelen_ilo_lasina::source_to_lasina(&formatted);
+        assert_eq!(
+            formatted,
+            concat!(
+                "len\u{3000}comments are kept: <jan>\n",
+                "ilo o toki e toki <\u{F1911} x> e toki <jan pona>\n",
+                "ilo o toki e toki <a>>b<<c> e nanpa <luka tu>\n",
+            )
+        );
+        assert_eq!(run(&formatted).1, output);
+    }
+}
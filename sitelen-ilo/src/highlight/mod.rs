@@ -0,0 +1,157 @@
+//! Highlighted output of programs, for web pages and editor plugins.
+
+use serde_json::json;
+
+use crate::parse::lex::Token;
+
+/// Prefix for the CSS class of each kind of token.
+const CLASS_PREFIX: &str = "ilo-";
+
+/// Where a token starts, as a line and a count of UTF-16 code units into it, which is what
+/// editors expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TokenPosition {
+    pub(crate) line: usize,
+    pub(crate) character: usize,
+    /// Length of the token in UTF-16 code units.
+    pub(crate) length: usize,
+}
+
+/// Finds the position of each token. The tokens must be in order.
+pub(crate) fn token_positions(source: &str, tokens: &[Token]) -> Vec<TokenPosition> {
+    let mut positions = Vec::with_capacity(tokens.len());
+    let (mut line, mut offset, mut character) = (0, 0, 0);
+    for token in tokens {
+        let before = &source[offset..token.span.offset];
+        if let Some(i) = before.rfind('\n') {
+            line += before.matches('\n').count();
+            character = 0;
+            offset += i + 1;
+        }
+        character += source[offset..token.span.offset].encode_utf16().count();
+        offset = token.span.offset;
+
+        let text = &source[offset..offset + token.span.len];
+        positions.push(TokenPosition {
+            line,
+            character,
+            length: text.encode_utf16().count(),
+        });
+    }
+    positions
+}
+
+fn escape_html(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Writes a program as HTML, with each token in a `<span>` whose class is named after its kind.
+pub(crate) fn to_html(source: &str, tokens: &[Token]) -> String {
+    let mut out = String::from("<pre class=\"sitelen-ilo\"><code>");
+    let mut offset = 0;
+    for token in tokens {
+        let end = token.span.offset + token.span.len;
+        escape_html(&mut out, &source[offset..token.span.offset]);
+        out.push_str("<span class=\"");
+        out.push_str(CLASS_PREFIX);
+        out.push_str(token.kind.name());
+        out.push_str("\">");
+        escape_html(&mut out, &source[token.span.offset..end]);
+        out.push_str("</span>");
+        offset = end;
+    }
+    escape_html(&mut out, &source[offset..]);
+    out.push_str("</code></pre>\n");
+    out
+}
+
+/// Writes the tokens of a program as a JSON array, with one object per token.
+pub(crate) fn to_json(source: &str, tokens: &[Token]) -> String {
+    let positions = token_positions(source, tokens);
+    let tokens: Vec<_> = tokens
+        .iter()
+        .zip(positions)
+        .map(|(token, pos)| {
+            let text = &source[token.span.offset..token.span.offset + token.span.len];
+            json!({
+                "kind": token.kind.name(),
+                "text": text,
+                "offset": token.span.offset,
+                "len": token.span.len,
+                "line": pos.line,
+                "character": pos.character,
+                "length": pos.length,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&tokens).expect("tokens are valid JSON") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        highlight::{TokenPosition, to_html, to_json, token_positions},
+        parse::lex::tokenize,
+    };
+
+    #[test]
+    fn test_positions() {
+        let source = concat!("a\n\t", sp!("o toki"), "\n\n", sp!("pini"));
+        let tokens = tokenize(source);
+        let positions = token_positions(source, &tokens);
+        let pos = |line, character, length| TokenPosition {
+            line,
+            character,
+            length,
+        };
+        assert_eq!(
+            positions,
+            [pos(0, 0, 1), pos(1, 1, 2), pos(1, 3, 2), pos(3, 0, 2)]
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let source = concat!("#!<o-ilo>\n", sp!("o pali"));
+        let expected = [
+            "<pre class=\"sitelen-ilo\"><code>",
+            "<span class=\"ilo-comment\">#!&lt;o-ilo&gt;</span>\n",
+            "<span class=\"ilo-keyword\">",
+            sp!("o"),
+            "</span><span class=\"ilo-keyword\">",
+            sp!("pali"),
+            "</span></code></pre>\n",
+        ];
+        assert_eq!(to_html(source, &tokenize(source)), expected.concat());
+    }
+
+    #[test]
+    fn test_json() {
+        let source = sp!("nanpa [ijo] o sin");
+        let tokens: Value = serde_json::from_str(&to_json(source, &tokenize(source))).unwrap();
+        assert_eq!(tokens.as_array().unwrap().len(), 4);
+        assert_eq!(
+            tokens[1],
+            json!({
+                "kind": "identifier",
+                "text": sp!("[ijo]"),
+                "offset": 4,
+                "len": 12,
+                "line": 0,
+                "character": 2,
+                "length": 6,
+            })
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Questions the language server can answer about an open document.
+
+use std::ops::Range;
+
+use sitelen_ilo_lasina::{tables::SP_TABLE, to_lasina};
+use sitelen_ilo_macros::{sp, sp_c};
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        object::{PrimitiveType, Variable},
+        statement::Target,
+    },
+    check::{check_ni, check_program},
+    diagnostic::Severity,
+    parse::{error::ParseError, parse_program, util::CharSitelenPonaExt},
+};
+
+const MSG_DECLARED: &str = "declared on line";
+const MSG_UNDECLARED: &str = "not declared here";
+
+/// A place in a document, as a line and a count of UTF-16 code units into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Position {
+    pub(super) line: usize,
+    pub(super) character: usize,
+}
+
+/// A problem found in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Diagnostic {
+    pub(super) range: Range<usize>,
+    pub(super) severity: Severity,
+    pub(super) message: String,
+}
+impl Diagnostic {
+    fn new(severity: Severity, err: &ParseError) -> Self {
+        let span = err.span();
+        let start = span.location_offset();
+        let message = match err.help() {
+            Some(help) => format!("{}\nhelp: {}", err.reason(), help),
+            None => err.reason().to_string(),
+        };
+        Self {
+            range: start..start + span.fragment().len(),
+            severity,
+            message,
+        }
+    }
+}
+
+/// A word that a typed prefix can be completed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Completion {
+    pub(super) word: &'static str,
+    pub(super) glyph: char,
+}
+
+fn type_of(c: char) -> Option<PrimitiveType> {
+    match c {
+        sp_c!("toki") => Some(PrimitiveType::Toki),
+        sp_c!("nanpa") => Some(PrimitiveType::Nanpa),
+        sp_c!("lon") => Some(PrimitiveType::Lon),
+        _ => None,
+    }
+}
+
+/// Checks if a statement declares a variable (`[variable]󱥄󱥝`).
+fn declaration_of(stmt: &Statement, var: &Variable) -> Option<SrcSpan> {
+    let Statement::Command(cmd) = stmt else {
+        return None;
+    };
+    let declares = cmd.actions.iter().any(|action| action.name == sp!("sin"));
+    match &cmd.target {
+        Some(Target::Variable(target)) if declares && target == var => Some(cmd.span),
+        _ => None,
+    }
+}
+
+/// Finds the declaration of a variable that is visible at `offset`. Declarations in blocks that
+/// don't contain `offset` are out of scope.
+fn find_declaration(stmts: &[Statement], var: &Variable, offset: usize) -> Option<SrcSpan> {
+    let count = stmts.partition_point(|stmt| stmt.offset() <= offset);
+    let (last, before) = stmts[..count].split_last()?;
+    let inner = match last {
+        Statement::IfLadder(ladder) => {
+            let count = ladder
+                .blocks
+                .partition_point(|block| block.start.span.offset <= offset);
+            ladder.blocks[..count]
+                .last()
+                .and_then(|block| find_declaration(&block.contents, var, offset))
+        }
+        Statement::LoopBlock(block) => find_declaration(&block.contents, var, offset),
+        _ => None,
+    };
+    inner.or_else(|| declaration_of(last, var)).or_else(|| {
+        before
+            .iter()
+            .rev()
+            .find_map(|stmt| declaration_of(stmt, var))
+    })
+}
+
+/// An open document, with its program parsed if it can be.
+pub(super) struct Document {
+    pub(super) text: String,
+    program: Option<Vec<Statement>>,
+}
+impl Document {
+    pub(super) fn new(text: String) -> Self {
+        let program = parse_program(&text).ok();
+        Self { text, program }
+    }
+
+    /// Finds the byte range of the line containing `offset`, without its line break.
+    fn line_range(&self, offset: usize) -> Range<usize> {
+        let start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |i| offset + i);
+        start..end
+    }
+
+    /// Converts a position to a byte offset, clamping it to the end of its line.
+    pub(super) fn offset(&self, pos: Position) -> usize {
+        let mut start = 0;
+        for _ in 0..pos.line {
+            match self.text[start..].find('\n') {
+                Some(i) => start += i + 1,
+                None => return self.text.len(),
+            }
+        }
+        let line = self.line_range(start);
+        let mut units = 0;
+        for (i, c) in self.text[line.clone()].char_indices() {
+            if units >= pos.character {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        line.end
+    }
+
+    /// Converts a byte offset to a position.
+    pub(super) fn position(&self, offset: usize) -> Position {
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            line: before.matches('\n').count(),
+            character: before[line_start..].encode_utf16().count(),
+        }
+    }
+
+    /// Finds every problem in the document.
+    pub(super) fn diagnostics(&self) -> Vec<Diagnostic> {
+        let program = match parse_program(&self.text) {
+            Ok(program) => program,
+            Err(errors) => {
+                return errors
+                    .iter()
+                    .map(|err| Diagnostic::new(Severity::Error, err))
+                    .collect();
+            }
+        };
+        let errors = check_program(&self.text, &program);
+        let warnings = check_ni(&self.text, &program);
+        let errors = errors
+            .iter()
+            .map(|err| Diagnostic::new(Severity::Error, err));
+        let warnings = warnings
+            .iter()
+            .map(|err| Diagnostic::new(Severity::Warning, err));
+        errors.chain(warnings).collect()
+    }
+
+    /// Finds the variable written at `offset`, with the range of its type and cartouche.
+    fn variable_at(&self, offset: usize) -> Option<(Variable, Range<usize>)> {
+        let line = self.line_range(offset);
+        let mut rest = self.text[offset..line.end].chars();
+        let open = match (rest.next(), rest.next()) {
+            (Some(c), Some(sp_c!("["))) if type_of(c).is_some() => offset + c.len_utf8(),
+            (Some(sp_c!("[")), _) => offset,
+            _ => self.text[line.start..offset].rfind(sp_c!("["))? + line.start,
+        };
+        let name_start = open + sp_c!("[").len_utf8();
+        if self.text[name_start.min(offset)..offset].contains(sp_c!("]")) {
+            return None;
+        }
+        let close = self.text[name_start..line.end].find(sp_c!("]"))? + name_start;
+        let type_word = self.text[..open].chars().next_back()?;
+        let var = Variable {
+            ptype: type_of(type_word)?,
+            name: self.text[name_start..close].to_string(),
+        };
+        let range = open - type_word.len_utf8()..close + sp_c!("]").len_utf8();
+        Some((var, range))
+    }
+
+    /// Finds where the variable at `offset` is declared.
+    fn declaration(&self, offset: usize) -> Option<(Variable, Option<Range<usize>>)> {
+        let (var, _) = self.variable_at(offset)?;
+        let span = self
+            .program
+            .as_ref()
+            .and_then(|program| find_declaration(program, &var, offset));
+        let range = span.map(|span| {
+            // point at the variable itself, rather than the whole statement
+            let written = format!(
+                "{}{}{}{}",
+                var.ptype.word(),
+                sp_c!("["),
+                var.name,
+                sp_c!("]")
+            );
+            let text = &self.text[span.offset..span.offset + span.len];
+            match text.find(&written) {
+                Some(i) => span.offset + i..span.offset + i + written.len(),
+                None => span.offset..span.offset + span.len,
+            }
+        });
+        Some((var, range))
+    }
+
+    /// Describes what is at `offset`, in Markdown, along with the range described.
+    pub(super) fn hover(&self, offset: usize) -> Option<(Range<usize>, String)> {
+        if let Some((var, range)) = self.variable_at(offset) {
+            let (_, declaration) = self.declaration(offset)?;
+            let site = match declaration {
+                Some(decl) => format!("{} {}", MSG_DECLARED, self.position(decl.start).line + 1),
+                None => MSG_UNDECLARED.to_string(),
+            };
+            let ptype = to_lasina(&var.ptype.word().to_string());
+            let contents = format!("**{}** [{}]\n\n{}", ptype, to_lasina(&var.name), site);
+            return Some((range, contents));
+        }
+
+        let glyph = self.text[offset..].chars().next().filter(|c| c.is_sp_word())?;
+        let word = to_lasina(&glyph.to_string());
+        Some((offset..offset + glyph.len_utf8(), format!("**{}**", word)))
+    }
+
+    /// Finds the declaration of the variable at `offset`.
+    pub(super) fn definition(&self, offset: usize) -> Option<Range<usize>> {
+        self.declaration(offset)?.1
+    }
+
+    /// Finds the Latin word being typed before `offset`, and the glyphs it could become.
+    pub(super) fn completions(&self, offset: usize) -> (Range<usize>, Vec<Completion>) {
+        let before = &self.text[..offset];
+        let start = before
+            .rfind(|c: char| !c.is_ascii_lowercase())
+            .map_or(0, |i| i + before[i..].chars().next().unwrap().len_utf8());
+        let prefix = &before[start..];
+        let mut words: Vec<_> = SP_TABLE
+            .entries()
+            .filter(|(word, _)| word.starts_with(prefix))
+            .map(|(word, glyph)| Completion {
+                word,
+                glyph: *glyph,
+            })
+            .collect();
+        words.sort_by_key(|completion| completion.word);
+        (start..offset, words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        diagnostic::Severity,
+        lsp::document::{Document, Position},
+    };
+
+    #[test]
+    fn test_positions() {
+        let doc = Document::new(format!("a\n{}b\n", sp!("toki")));
+        let pos = |line, character| Position { line, character };
+        assert_eq!(doc.offset(pos(1, 0)), 2);
+        // glyphs outside the BMP take two UTF-16 code units
+        assert_eq!(doc.offset(pos(1, 2)), 6);
+        assert_eq!(doc.offset(pos(1, 9)), 7);
+        assert_eq!(doc.offset(pos(5, 0)), doc.text.len());
+        assert_eq!(doc.position(6), pos(1, 2));
+        assert_eq!(doc.position(doc.text.len()), pos(2, 0));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let doc = Document::new(
+            [
+                sp!("nanpa [ijo] o sin"),
+                sp!("ilo o toki e nanpa ni"),
+                sp!("nanpa [ijo] o toki <a>"),
+            ]
+            .join("\n"),
+        );
+        let diagnostics = doc.diagnostics();
+        let severities: Vec<_> = diagnostics.iter().map(|diag| diag.severity).collect();
+        assert_eq!(severities, [Severity::Error, Severity::Warning]);
+        assert_eq!(doc.position(diagnostics[0].range.start).line, 2);
+
+        let doc = Document::new(sp!("ilo o toki e").to_string());
+        assert_eq!(doc.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn test_hover() {
+        let source = [
+            sp!("nanpa [ijo] o sin"),
+            sp!("o pali"),
+            sp!("nanpa [ijo] o sin"),
+            sp!("pini"),
+            sp!("ilo o toki e nanpa [ijo]"),
+        ]
+        .join("\n");
+        let doc = Document::new(source.clone());
+
+        let (range, text) = doc.hover(0).unwrap();
+        assert_eq!(&source[range], sp!("nanpa [ijo]"));
+        assert_eq!(text, "**nanpa** [ijo]\n\ndeclared on line 1");
+
+        let toki = source.rfind(sp!("toki")).unwrap();
+        assert_eq!(doc.hover(toki).unwrap().1, "**toki**");
+        assert_eq!(doc.hover(source.len()), None);
+
+        // the declaration in the block is out of scope
+        let use_site = source.rfind(sp!("[ijo]")).unwrap();
+        let decl = doc.definition(use_site).unwrap();
+        assert_eq!(decl, 0..sp!("nanpa [ijo]").len());
+        let inner = source.find(sp!("o pali")).unwrap() + sp!("o pali").len() + 1;
+        assert_eq!(doc.definition(inner + 8).unwrap().start, inner);
+        assert_eq!(doc.definition(toki), None);
+    }
+
+    #[test]
+    fn test_completions() {
+        let doc = Document::new(format!("{}tok", sp!("ilo o")));
+        let (range, words) = doc.completions(doc.text.len());
+        assert_eq!(&doc.text[range], "tok");
+        let words: Vec<_> = words.iter().map(|completion| completion.word).collect();
+        assert_eq!(words, ["toki"]);
+    }
+}
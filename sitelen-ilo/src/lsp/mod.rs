@@ -0,0 +1,491 @@
+//! A language server, speaking the Language Server Protocol over standard input and output.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    ops::Range,
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    diagnostic::Severity,
+    highlight::token_positions,
+    lsp::{
+        document::{Document, Position},
+        rpc::{read_message, write_message},
+    },
+    parse::lex::{TokenKind, tokenize},
+};
+
+mod document;
+mod rpc;
+
+const SERVER_NAME: &str = "o-ilo";
+
+const CODE_PARSE_ERROR: i64 = -32700;
+const CODE_INVALID_PARAMS: i64 = -32602;
+const CODE_METHOD_NOT_FOUND: i64 = -32601;
+const ERR_PARSE_ERROR: &str = "parse error";
+const ERR_INVALID_PARAMS: &str = "invalid parameters";
+const ERR_METHOD_NOT_FOUND: &str = "method not found";
+
+/// Documents are sent whole on every change.
+const SYNC_FULL: u8 = 1;
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const COMPLETION_KEYWORD: u8 = 14;
+
+/// Semantic token types, in the order given to the client.
+const TOKEN_TYPES: [&str; 8] = [
+    "type", "keyword", "operator", "variable", "string", "number", "comment", "function",
+];
+
+/// Finds the index of a kind of token in [`TOKEN_TYPES`]. Unknown text isn't highlighted.
+fn token_type(kind: TokenKind) -> Option<usize> {
+    match kind {
+        TokenKind::Type => Some(0),
+        TokenKind::Keyword => Some(1),
+        TokenKind::Preposition => Some(2),
+        TokenKind::Identifier => Some(3),
+        TokenKind::Literal => Some(4),
+        TokenKind::Number => Some(5),
+        TokenKind::Comment => Some(6),
+        TokenKind::Word => Some(7),
+        TokenKind::Unknown => None,
+    }
+}
+
+/// An error to send back in response to a request.
+struct RpcError {
+    code: i64,
+    message: &'static str,
+}
+
+const INVALID_PARAMS: RpcError = RpcError {
+    code: CODE_INVALID_PARAMS,
+    message: ERR_INVALID_PARAMS,
+};
+const PARSE_ERROR: RpcError = RpcError {
+    code: CODE_PARSE_ERROR,
+    message: ERR_PARSE_ERROR,
+};
+
+fn position_json(pos: Position) -> Value {
+    json!({ "line": pos.line, "character": pos.character })
+}
+
+fn range_json(doc: &Document, range: Range<usize>) -> Value {
+    json!({
+        "start": position_json(doc.position(range.start)),
+        "end": position_json(doc.position(range.end)),
+    })
+}
+
+fn uri_of(params: &Value) -> Result<&str, RpcError> {
+    params["textDocument"]["uri"].as_str().ok_or(INVALID_PARAMS)
+}
+
+fn position_of(params: &Value) -> Result<Position, RpcError> {
+    let pos = &params["position"];
+    match (pos["line"].as_u64(), pos["character"].as_u64()) {
+        (Some(line), Some(character)) => Ok(Position {
+            line: line as usize,
+            character: character as usize,
+        }),
+        _ => Err(INVALID_PARAMS),
+    }
+}
+
+/// The state of the server between messages.
+struct Server<W: Write> {
+    output: W,
+    documents: HashMap<String, Document>,
+}
+impl<W: Write> Server<W> {
+    /// Sends the problems in a document to the client.
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = match self.documents.get(uri) {
+            Some(doc) => doc
+                .diagnostics()
+                .into_iter()
+                .map(|diag| {
+                    let severity = match diag.severity {
+                        Severity::Error => SEVERITY_ERROR,
+                        Severity::Warning => SEVERITY_WARNING,
+                    };
+                    json!({
+                        "range": range_json(doc, diag.range),
+                        "severity": severity,
+                        "source": SERVER_NAME,
+                        "message": diag.message,
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let message = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        });
+        write_message(&mut self.output, &message)
+    }
+
+    /// Finds the document and offset a request is about.
+    fn locate(&self, params: &Value) -> Result<Option<(&Document, usize)>, RpcError> {
+        let uri = uri_of(params)?;
+        let pos = position_of(params)?;
+        Ok(self.documents.get(uri).map(|doc| (doc, doc.offset(pos))))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, RpcError> {
+        let Some((doc, offset)) = self.locate(params)? else {
+            return Ok(Value::Null);
+        };
+        Ok(match doc.hover(offset) {
+            Some((range, contents)) => json!({
+                "contents": { "kind": "markdown", "value": contents },
+                "range": range_json(doc, range),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, RpcError> {
+        let Some((doc, offset)) = self.locate(params)? else {
+            return Ok(Value::Null);
+        };
+        Ok(match doc.definition(offset) {
+            Some(range) => json!({ "uri": uri_of(params)?, "range": range_json(doc, range) }),
+            None => Value::Null,
+        })
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, RpcError> {
+        let Some((doc, offset)) = self.locate(params)? else {
+            return Ok(Value::Null);
+        };
+        let (range, completions) = doc.completions(offset);
+        let range = range_json(doc, range);
+        let items: Vec<_> = completions
+            .iter()
+            .map(|completion| {
+                let glyph = completion.glyph.to_string();
+                json!({
+                    "label": completion.word,
+                    "kind": COMPLETION_KEYWORD,
+                    "detail": glyph,
+                    "filterText": completion.word,
+                    "textEdit": { "range": range, "newText": glyph },
+                })
+            })
+            .collect();
+        Ok(json!(items))
+    }
+
+    fn semantic_tokens(&self, params: &Value) -> Result<Value, RpcError> {
+        let Some(doc) = self.documents.get(uri_of(params)?) else {
+            return Ok(Value::Null);
+        };
+        let tokens = tokenize(&doc.text);
+        let positions = token_positions(&doc.text, &tokens);
+
+        // each token is relative to the one before it
+        let mut data = Vec::new();
+        let (mut line, mut character) = (0, 0);
+        for (token, pos) in tokens.iter().zip(positions) {
+            let Some(token_type) = token_type(token.kind) else {
+                continue;
+            };
+            if pos.line != line {
+                character = 0;
+            }
+            data.extend([
+                pos.line - line,
+                pos.character - character,
+                pos.length,
+                token_type,
+                0,
+            ]);
+            (line, character) = (pos.line, pos.character);
+        }
+        Ok(json!({ "data": data }))
+    }
+
+    /// Answers a request.
+    fn request(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "semanticTokensProvider": {
+                        "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                        "full": true,
+                    },
+                },
+                "serverInfo": { "name": SERVER_NAME },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            _ => Err(RpcError {
+                code: CODE_METHOD_NOT_FOUND,
+                message: ERR_METHOD_NOT_FOUND,
+            }),
+        }
+    }
+
+    /// Handles a notification. Notifications that aren't understood are ignored.
+    fn notify(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let Ok(uri) = uri_of(params) else {
+            return Ok(());
+        };
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // only the last change matters, since each one is the whole document
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return self.publish_diagnostics(uri);
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Ok(());
+        };
+        let uri = uri.to_string();
+        self.documents
+            .insert(uri.clone(), Document::new(text.to_string()));
+        self.publish_diagnostics(&uri)
+    }
+
+    /// Handles one message, returning `false` once the client asks the server to exit.
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let Some(method) = message["method"].as_str() else {
+            // responses to requests the server never makes
+            return Ok(true);
+        };
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            if method == "exit" {
+                return Ok(false);
+            }
+            self.notify(method, params)?;
+            return Ok(true);
+        };
+
+        match self.request(method, params) {
+            Ok(result) => {
+                let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                write_message(&mut self.output, &response)?;
+            }
+            Err(err) => self.respond_error(id, err)?,
+        }
+        Ok(true)
+    }
+
+    /// Sends an error in response to a request.
+    fn respond_error(&mut self, id: &Value, err: RpcError) -> io::Result<()> {
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        });
+        write_message(&mut self.output, &response)
+    }
+}
+
+/// Runs the language server until the client exits or the input ends.
+pub(crate) fn run(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+    };
+    while let Some(message) = read_message(&mut input)? {
+        let message = match message {
+            Ok(message) => message,
+            // the id can't be known, so the error goes to no request in particular
+            Err(_) => {
+                server.respond_error(&Value::Null, PARSE_ERROR)?;
+                continue;
+            }
+        };
+        if !server.handle(&message)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+    use sitelen_ilo_macros::sp;
+
+    use crate::lsp::{
+        rpc::{read_message, write_message},
+        run,
+    };
+
+    const URI: &str = "file:///musi.lipu";
+
+    /// Runs the server over a script of messages, returning everything it sends back.
+    fn session(script: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in script {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        run(&input[..], &mut output).expect("server should not fail");
+
+        let mut replies = Vec::new();
+        let mut output = &output[..];
+        while let Some(message) = read_message(&mut output).unwrap() {
+            replies.push(message.unwrap());
+        }
+        replies
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    fn at(line: u64, character: u64) -> Value {
+        json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn test_session() {
+        let text = [sp!("nanpa [ijo] o sin"), sp!("ilo o toki e nanpa [ijo]")].join("\n");
+        let replies = session(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": URI, "languageId": "sitelen-ilo", "version": 1, "text": text } }),
+            ),
+            request(2, "textDocument/hover", at(1, 4)),
+            request(3, "textDocument/definition", at(1, 12)),
+            request(4, "textDocument/completion", at(1, 0)),
+            request(5, "sitelen/unknown", json!({})),
+            request(6, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+            request(7, "shutdown", Value::Null),
+        ]);
+
+        assert_eq!(replies.len(), 7);
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        assert_eq!(replies[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+
+        // ilo o toki: the glyph at UTF-16 offset 4 is toki
+        assert_eq!(replies[2]["result"]["contents"]["value"], "**toki**");
+        assert_eq!(
+            replies[2]["result"]["range"],
+            json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 6 } })
+        );
+
+        assert_eq!(replies[3]["result"]["uri"], URI);
+        assert_eq!(
+            replies[3]["result"]["range"],
+            json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 8 } })
+        );
+
+        let items = replies[4]["result"].as_array().unwrap();
+        assert!(items.iter().any(|item| item["label"] == "pona"));
+
+        assert_eq!(replies[5]["error"]["code"], -32601);
+        assert_eq!(replies[6]["id"], 6);
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let mut input = b"Content-Length: 5\r\n\r\n{bad}".to_vec();
+        write_message(&mut input, &request(1, "shutdown", Value::Null)).unwrap();
+        let mut output = Vec::new();
+        run(&input[..], &mut output).expect("server should keep running");
+
+        let mut output = &output[..];
+        let first = read_message(&mut output).unwrap().unwrap().unwrap();
+        assert_eq!(first["id"], Value::Null);
+        assert_eq!(first["error"]["code"], -32700);
+        let second = read_message(&mut output).unwrap().unwrap().unwrap();
+        assert_eq!(second["id"], 1);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let replies = session(&[
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": URI, "text": sp!("ilo o toki e nanpa ni") } }),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({ "textDocument": { "uri": URI }, "contentChanges": [{ "text": sp!("o pali") }] }),
+            ),
+            request(1, "textDocument/completion", at(0, 4)),
+            notification(
+                "textDocument/didClose",
+                json!({ "textDocument": { "uri": URI } }),
+            ),
+        ]);
+
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["severity"], 2);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 0, "character": 6 })
+        );
+
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+
+        // the completion is after the document changed, and covers every word
+        assert_eq!(replies[2]["id"], 1);
+        assert!(replies[2]["result"].as_array().unwrap().len() > 100);
+
+        assert_eq!(replies[3]["params"]["diagnostics"], json!([]));
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let text = concat!(sp!("nanpa [ijo] o sin"), "\n\t", sp!("len"), " x");
+        let replies = session(&[
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": URI, "text": text } }),
+            ),
+            request(
+                1,
+                "textDocument/semanticTokens/full",
+                json!({ "textDocument": { "uri": URI } }),
+            ),
+        ]);
+        let expected = [
+            [0, 0, 2, 0, 0],
+            [0, 2, 6, 3, 0],
+            [0, 6, 2, 1, 0],
+            [0, 2, 2, 7, 0],
+            [1, 1, 2, 1, 0],
+            [0, 3, 1, 6, 0],
+        ];
+        assert_eq!(replies[1]["result"]["data"], json!(expected.concat()));
+    }
+}
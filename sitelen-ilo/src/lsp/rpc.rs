@@ -0,0 +1,74 @@
+//! Framing of JSON-RPC messages, as used by the Language Server Protocol.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+const ERR_NO_LENGTH: &str = "message has no Content-Length header";
+const ERR_BAD_LENGTH: &str = "Content-Length is not a number";
+
+/// Reads the next message, or `None` if the input has ended. Only errors in the framing are
+/// returned as I/O errors; a body that isn't valid JSON is returned as the inner error, so that
+/// the message can be skipped.
+pub(super) fn read_message(
+    input: &mut impl BufRead,
+) -> io::Result<Option<serde_json::Result<Value>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            let value = value.trim().parse::<usize>();
+            length = Some(value.map_err(|_| io::Error::other(ERR_BAD_LENGTH))?);
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::other(ERR_NO_LENGTH))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)))
+}
+
+/// Writes a message with its header.
+pub(super) fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::lsp::rpc::{read_message, write_message};
+
+    #[test]
+    fn test_framing() {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({"id": 1, "text": "󱥄"})).unwrap();
+        write_message(&mut out, &json!(null)).unwrap();
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert!(text.starts_with("Content-Length: 22\r\n\r\n{"));
+
+        let mut input = &out[..];
+        let first = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(first.unwrap(), json!({"id": 1, "text": "󱥄"}));
+        let second = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(second.unwrap(), json!(null));
+        assert!(read_message(&mut input).unwrap().is_none());
+
+        let mut input = "Content-Length: 5\r\n\r\n{bad}".as_bytes();
+        assert!(read_message(&mut input).unwrap().unwrap().is_err());
+
+        let mut input = "Content-Type: x\r\n\r\n{}".as_bytes();
+        assert!(read_message(&mut input).is_err());
+    }
+}
@@ -1,10 +1,309 @@
+use std::{io::IsTerminal, process::ExitCode};
+
 use clap::Parser;
 
+use sitelen_ilo_lasina::Transliteration;
+
+use crate::{
+    ast::{SrcSpan, block::Statement},
+    diagnostic::{Renderer, Severity},
+    parse::error::ParseError,
+};
+
 mod cli;
 mod ast;
+mod check;
+mod diagnostic;
+mod format;
+mod highlight;
+mod lsp;
+mod optimize;
 mod parse;
+mod repl;
+mod runtime;
+mod transpile;
+
+/// Files with this extension are written in sitelen Lasina.
+const LASINA_EXTENSION: &str = ".lipu.txt";
+const ERR_UNKNOWN_WORD: &str = "not a word that can be written in sitelen pona";
+
+/// Reads a source file, printing an error if it can't be read.
+fn read_source(path: &str) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => Some(source),
+        Err(err) => {
+            eprintln!("{}: error: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Prints a program in sitelen Lasina.
+fn print_lasina(path: &str) -> ExitCode {
+    let Some(source) = read_source(path) else {
+        return ExitCode::FAILURE;
+    };
+    print!("{}", sitelen_ilo_lasina::to_lasina(&source));
+    ExitCode::SUCCESS
+}
+
+/// Transliterates a program written in sitelen Lasina, printing an error if it can't be.
+fn transliterate(source: &str, renderer: &Renderer) -> Option<Transliteration> {
+    match sitelen_ilo_lasina::source_to_ucsur(source) {
+        Ok(transliteration) => Some(transliteration),
+        Err(err) => {
+            let span = SrcSpan {
+                offset: err.offset,
+                len: err.word.len(),
+            };
+            let err = ParseError::new(span.locate(source), ERR_UNKNOWN_WORD);
+            eprint!("{}", renderer.render(Severity::Error, &err));
+            None
+        }
+    }
+}
 
-fn main() {
+/// A program read from a file and parsed.
+struct Loaded {
+    path: String,
+    /// The text of the file.
+    source: String,
+    /// How the file was transliterated, if it is written in sitelen Lasina.
+    transliteration: Option<Transliteration>,
+    program: Vec<Statement>,
+}
+impl Loaded {
+    /// Gets the text the program was parsed from.
+    fn code(&self) -> &str {
+        match &self.transliteration {
+            Some(transliteration) => &transliteration.text,
+            None => &self.source,
+        }
+    }
+
+    /// Gets a renderer for errors in the program.
+    fn renderer(&self) -> Renderer<'_> {
+        // only use colours when a person is likely to be reading
+        let renderer = Renderer::new(&self.path, &self.source, std::io::stderr().is_terminal());
+        match &self.transliteration {
+            Some(transliteration) => renderer.with_transliteration(transliteration),
+            None => renderer,
+        }
+    }
+}
+
+/// Reads and parses a program, transliterating it first if it is written in sitelen Lasina.
+/// Prints any errors.
+fn load_program(path: &str, lasina: bool) -> Option<Loaded> {
+    let source = read_source(path)?;
+    let transliteration = match lasina || path.ends_with(LASINA_EXTENSION) {
+        true => {
+            let renderer = Renderer::new(path, &source, std::io::stderr().is_terminal());
+            Some(transliterate(&source, &renderer)?)
+        }
+        false => None,
+    };
+    let mut loaded = Loaded {
+        path: path.to_string(),
+        source,
+        transliteration,
+        program: Vec::new(),
+    };
+    loaded.program = parse(loaded.code(), &loaded.renderer())?;
+    Some(loaded)
+}
+
+/// Checks a program before it is run or compiled, printing any errors. Returns whether it passed.
+fn check_loaded(loaded: &Loaded, program: &[Statement], renderer: &Renderer) -> bool {
+    let errors = check::check_program(loaded.code(), program);
+    for err in &errors {
+        eprint!("{}", renderer.render(Severity::Error, err));
+    }
+    errors.is_empty()
+}
+
+/// Parses a program, printing any syntax errors.
+fn parse(source: &str, renderer: &Renderer) -> Option<Vec<Statement>> {
+    match parse::parse_program(source) {
+        Ok(program) => Some(program),
+        Err(errors) => {
+            for err in &errors {
+                eprint!("{}", renderer.render(Severity::Error, err));
+            }
+            eprint!("{}", renderer.render_count(Severity::Error, errors.len(), "syntax error"));
+            None
+        }
+    }
+}
+
+/// Formats a program in place. With `check`, only reports whether it is formatted.
+fn format_file(path: &str, check: bool, lasina: bool) -> ExitCode {
+    let Some(loaded) = load_program(path, lasina) else {
+        return ExitCode::FAILURE;
+    };
+    let mut formatted = format::format_program(loaded.code(), &loaded.program);
+    if loaded.transliteration.is_some() {
+        formatted = sitelen_ilo_lasina::source_to_lasina(&formatted);
+    }
+
+    if formatted == loaded.source {
+        return ExitCode::SUCCESS;
+    }
+    if check {
+        eprintln!("{}: not formatted", path);
+        return ExitCode::FAILURE;
+    }
+    match std::fs::write(path, formatted) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}: error: {}", path, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints a program with its syntax highlighted.
+fn highlight_file(path: &str, format: cli::HighlightFormat) -> ExitCode {
+    let Some(source) = read_source(path) else {
+        return ExitCode::FAILURE;
+    };
+    let tokens = parse::lex::tokenize(&source);
+    match format {
+        cli::HighlightFormat::Html => print!("{}", highlight::to_html(&source, &tokens)),
+        cli::HighlightFormat::Json => print!("{}", highlight::to_json(&source, &tokens)),
+    }
+    ExitCode::SUCCESS
+}
+
+/// Compiles a program to another language, writing it next to the input unless `output` is given.
+/// The program is only optimised if `optimize` is set.
+fn build_file(
+    path: &str,
+    target: cli::BuildTarget,
+    output: Option<&str>,
+    lasina: bool,
+    optimize: bool,
+) -> ExitCode {
+    let Some(mut loaded) = load_program(path, lasina) else {
+        return ExitCode::FAILURE;
+    };
+    let program = std::mem::take(&mut loaded.program);
+    if !check_loaded(&loaded, &program, &loaded.renderer()) {
+        return ExitCode::FAILURE;
+    }
+
+    let program = match optimize {
+        true => optimize::optimize(program),
+        false => program,
+    };
+    let (built, extension) = match target {
+        cli::BuildTarget::C => (
+            transpile::c::to_c(&program, path, &loaded.source, loaded.transliteration.as_ref()),
+            "c",
+        ),
+    };
+
+    let output = match output {
+        Some(output) => output.to_string(),
+        None => {
+            let stem = path.strip_suffix(LASINA_EXTENSION).unwrap_or(path);
+            std::path::Path::new(stem)
+                .with_extension(extension)
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    match std::fs::write(&output, built) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}: error: {}", output, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main() -> ExitCode {
     let args = cli::Cli::parse();
     args.preinit_all();
+
+    if let Some(command) = &args.command {
+        return match command {
+            cli::Command::Lasina { input } => print_lasina(input),
+            cli::Command::Fmt {
+                input,
+                check,
+                lasina,
+            } => format_file(input, *check, *lasina),
+            cli::Command::Highlight { input, format } => highlight_file(input, *format),
+            cli::Command::Build {
+                input,
+                target,
+                output,
+                lasina,
+                optimize,
+            } => build_file(input, *target, output.as_deref(), *lasina, *optimize),
+            cli::Command::Lsp => match lsp::run(std::io::stdin().lock(), std::io::stdout().lock()) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    ExitCode::FAILURE
+                }
+            },
+        };
+    }
+
+    let Some(input) = &args.input else {
+        let mut runtime = runtime::Runtime::new();
+        return match repl::run(&mut runtime, std::io::stderr().is_terminal()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        };
+    };
+
+    let Some(mut loaded) = load_program(input, args.lasina) else {
+        return ExitCode::FAILURE;
+    };
+    let program = std::mem::take(&mut loaded.program);
+    let (source, renderer) = (loaded.code(), loaded.renderer());
+
+    if !check_loaded(&loaded, &program, &renderer) {
+        return ExitCode::FAILURE;
+    }
+
+    let warnings = check::check_ni(source, &program);
+    let severity = match args.strict {
+        true => Severity::Error,
+        false => Severity::Warning,
+    };
+    for warning in &warnings {
+        eprint!("{}", renderer.render(severity, warning));
+    }
+    if args.strict && !warnings.is_empty() {
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(cli::Emit::OptimizedAst) = args.emit {
+        let program = optimize::optimize(program);
+        print!("{}", format::format_program(source, &program));
+        return ExitCode::SUCCESS;
+    }
+
+    // the unoptimised tree is the reference, so optimising is left to the user
+    let program = match args.optimize {
+        true => optimize::optimize(program),
+        false => program,
+    };
+
+    let result = match args.backend {
+        cli::Backend::Tree => runtime::Runtime::new().run(&program),
+        cli::Backend::Vm => runtime::vm::Vm::new().run(&runtime::vm::compile(&program)),
+    };
+    if let Err(err) = result {
+        eprint!("{}", renderer.render(Severity::Error, &err.locate(source)));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
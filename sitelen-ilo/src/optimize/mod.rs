@@ -0,0 +1,386 @@
+//! Optimisation of programs before they run.
+//!
+//! Intrinsics with literal arguments are run ahead of time, conditions between literals are
+//! decided, and code that can never run is removed. Folded intrinsics become *sona* commands, so
+//! that *ni* holds the same values as before. Anything that would fail is left for the runtime,
+//! so that errors are still raised in the same place.
+
+use sitelen_ilo_macros::sp;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::{IfBlock, IfLadder, Statement},
+        condition::Condition,
+        object::{Literal, Object, TypedValue},
+        statement::{Action, ArgKey, Argument, Command, LoopControl, Target},
+    },
+    runtime::intrinsic,
+};
+
+/// The value of *ni*, if it is known ahead of time.
+type KnownNi = Option<Literal>;
+
+/// Replaces a reference to *ni* with its value, if it is known and has the right type.
+fn substitute(obj: &mut Object, ni: &KnownNi) {
+    if let (Object::Ni(ni_ref), Some(value)) = (&*obj, ni)
+        && value.get_type() == ni_ref.ptype
+    {
+        *obj = Object::Literal(value.clone());
+    }
+}
+
+fn substitute_condition(cond: &mut Condition, ni: &KnownNi) {
+    let objects = match cond {
+        Condition::Equal(cond) => vec![&mut cond.a, &mut cond.b],
+        Condition::Greater(cond) => vec![&mut cond.a, &mut cond.b],
+        Condition::Less(cond) => vec![&mut cond.a, &mut cond.b],
+        Condition::AndEqual(cond) => cond.ps.iter_mut().chain([&mut cond.q]).collect(),
+        Condition::OrEqual(cond) => cond.ps.iter_mut().chain([&mut cond.q]).collect(),
+    };
+    for obj in objects {
+        substitute(obj, ni);
+    }
+}
+
+/// Evaluates a condition between literals. Returns `None` if it uses other objects, or if
+/// evaluating it would fail.
+fn evaluate(cond: &Condition) -> Option<bool> {
+    let literal = |obj: &Object| match obj {
+        Object::Literal(lit) => Some(lit.clone()),
+        _ => None,
+    };
+    let nanpa = |obj: &Object| match literal(obj)? {
+        Literal::Nanpa(value) => Some(value),
+        _ => None,
+    };
+    let lon = |obj: &Object| match literal(obj)? {
+        Literal::Lon(value) => Some(value),
+        _ => None,
+    };
+    let lon_values = |ps: &[Object], q: &Object| {
+        let ps = ps.iter().map(lon).collect::<Option<Vec<_>>>()?;
+        Some((ps, lon(q)?))
+    };
+
+    match cond {
+        Condition::Equal(cond) => {
+            let (a, b) = (literal(&cond.a)?, literal(&cond.b)?);
+            (a.get_type() == b.get_type()).then(|| a == b)
+        }
+        Condition::Greater(cond) => Some(nanpa(&cond.a)? > nanpa(&cond.b)?),
+        Condition::Less(cond) => Some(nanpa(&cond.a)? < nanpa(&cond.b)?),
+        Condition::AndEqual(cond) => {
+            let (ps, q) = lon_values(&cond.ps, &cond.q)?;
+            Some(ps.into_iter().all(|p| p) == q)
+        }
+        Condition::OrEqual(cond) => {
+            let (ps, q) = lon_values(&cond.ps, &cond.q)?;
+            Some(ps.into_iter().any(|p| p) == q)
+        }
+    }
+}
+
+/// Folds an optional condition, treating a missing condition as true. If the result is known
+/// ahead of time, the condition is removed and the result is returned.
+fn fold_condition(cond: &mut Option<Condition>, ni: &KnownNi) -> Option<bool> {
+    let Some(inner) = cond else {
+        return Some(true);
+    };
+    substitute_condition(inner, ni);
+    let value = evaluate(inner)?;
+    *cond = None;
+    Some(value)
+}
+
+/// Makes an action that sets *ni* to a value.
+fn sona(value: Literal, span: SrcSpan) -> Action {
+    Action {
+        name: sp!("sona").to_string(),
+        args: vec![Argument {
+            key: ArgKey::E,
+            value: Object::Literal(value),
+            span,
+        }],
+        span,
+    }
+}
+
+fn optimize_action(target: &Option<Target>, action: &mut Action, ni: &mut KnownNi) {
+    for arg in &mut action.args {
+        substitute(&mut arg.value, ni);
+    }
+    match target {
+        None => {
+            *ni = intrinsic::fold(&action.name, &action.args, action.span);
+            if let Some(value) = ni {
+                *action = sona(value.clone(), action.span);
+            }
+        }
+        Some(Target::Ilo) if action.name == sp!("wile linja") => *ni = None,
+        Some(_) => (),
+    }
+}
+
+/// Optimises a statement, returning `None` if it can be removed.
+fn optimize_statement(stmt: Statement, ni: &mut KnownNi) -> Option<Statement> {
+    match stmt {
+        Statement::Comment(_) => Some(stmt),
+        Statement::Command(mut cmd) => {
+            let always = fold_condition(&mut cmd.condition, ni);
+            if always == Some(false) {
+                return None;
+            }
+            let mut after = ni.clone();
+            for action in &mut cmd.actions {
+                optimize_action(&cmd.target, action, &mut after);
+            }
+            // the condition could not be decided, so the command might not run
+            if always.is_none() && after != *ni {
+                *ni = None;
+            } else {
+                *ni = after;
+            }
+            Some(Statement::Command(cmd))
+        }
+        Statement::ConditionEval(mut eval) => {
+            substitute_condition(&mut eval.condition, ni);
+            let Some(value) = evaluate(&eval.condition) else {
+                *ni = None;
+                return Some(Statement::ConditionEval(eval));
+            };
+            *ni = Some(Literal::Lon(value));
+            Some(Statement::Command(Command {
+                condition: None,
+                target: None,
+                actions: vec![sona(Literal::Lon(value), eval.span)],
+                span: eval.span,
+            }))
+        }
+        Statement::IfLadder(ladder) => {
+            let entry = ni.clone();
+            let mut blocks: Vec<IfBlock> = Vec::new();
+            // the values ni might have after the ladder
+            let mut exits = Vec::new();
+            let mut exhaustive = false;
+            for mut block in ladder.blocks {
+                let always = fold_condition(&mut block.start.condition, &entry);
+                if always == Some(false) {
+                    continue;
+                }
+                let mut block_ni = entry.clone();
+                block.contents = optimize_block(block.contents, &mut block_ni);
+                block.start.chained = !blocks.is_empty();
+                blocks.push(block);
+                exits.push(block_ni);
+                if always == Some(true) {
+                    exhaustive = true;
+                    break;
+                }
+            }
+            if !exhaustive {
+                exits.push(entry);
+            }
+            *ni = match exits.split_first() {
+                Some((first, rest)) if rest.iter().all(|exit| exit == first) => first.clone(),
+                _ => None,
+            };
+            (!blocks.is_empty()).then_some(Statement::IfLadder(IfLadder { blocks }))
+        }
+        Statement::LoopBlock(mut block) => {
+            // the first check uses the value of ni from before the loop
+            let mut first = block.start.condition.clone();
+            if fold_condition(&mut first, ni) == Some(false) {
+                return None;
+            }
+            fold_condition(&mut block.start.condition, &None);
+            block.contents = optimize_block(block.contents, &mut None);
+            *ni = None;
+            Some(Statement::LoopBlock(block))
+        }
+        Statement::LoopControl(mut control) => match fold_condition(&mut control.condition, ni) {
+            Some(false) => None,
+            _ => Some(Statement::LoopControl(control)),
+        },
+    }
+}
+
+/// Optimises statements in order. Statements after an unconditional loop control statement are
+/// removed, since they can never run.
+fn optimize_block(stmts: Vec<Statement>, ni: &mut KnownNi) -> Vec<Statement> {
+    let mut out = Vec::with_capacity(stmts.len());
+    for stmt in stmts {
+        let Some(stmt) = optimize_statement(stmt, ni) else {
+            continue;
+        };
+        let exits = matches!(
+            stmt,
+            Statement::LoopControl(LoopControl {
+                condition: None,
+                ..
+            })
+        );
+        out.push(stmt);
+        if exits {
+            break;
+        }
+    }
+    out
+}
+
+/// Optimises a program. The program must have passed [`crate::check::check_program`].
+pub(crate) fn optimize(program: Vec<Statement>) -> Vec<Statement> {
+    optimize_block(program, &mut None)
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        format::format_program, optimize::optimize, parse::parse_program, runtime::Runtime,
+        runtime::io::Streams,
+    };
+
+    fn format(source: &str) -> String {
+        let program = parse_program(source).expect("program should parse");
+        format_program(source, &program)
+    }
+
+    /// Checks that a program optimises to the same thing as another.
+    fn check(source: &[&str], expected: &[&str]) {
+        let source = source.join("\n");
+        let program = parse_program(&source).expect("program should parse");
+        let optimized = format_program(&source, &optimize(program));
+        assert_eq!(optimized, format(&expected.join("\n")));
+    }
+
+    #[test]
+    fn test_fold_intrinsics() {
+        check(
+            &[
+                sp!("o wan e nanpa <tu> e nanpa <wan>"),
+                sp!("o mute e nanpa ni e nanpa <tu>"),
+                sp!("nanpa [ijo] o nanpa ni"),
+                sp!("o wan linja e toki <jan> e toki <pona>"),
+                sp!("ilo o toki e toki ni e nanpa ni"),
+            ],
+            &[
+                sp!("o sona e nanpa <tu wan>"),
+                sp!("o sona e nanpa <luka wan>"),
+                sp!("nanpa [ijo] o nanpa <luka wan>"),
+                sp!("o sona e toki <jan pona>"),
+                sp!("ilo o toki e toki <jan pona> e nanpa ni"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_keep_failures() {
+        // errors are left for the runtime
+        let source = [
+            sp!("o kipisi e nanpa <wan> tawa nanpa <ala>"),
+            sp!("ken la toki <wan> li suli tawa nanpa <wan>"),
+            sp!("ken la nanpa ni li toki <wan>"),
+            sp!("ilo o wile linja"),
+            sp!("o wan e nanpa ni e nanpa <wan>"),
+        ];
+        check(&source, &source);
+    }
+
+    #[test]
+    fn test_conditions() {
+        check(
+            &[
+                sp!("nanpa <wan> li suli tawa nanpa <tu> la ilo o pini linja"),
+                sp!("nanpa <wan> li lili tawa nanpa <tu> la ilo o toki e toki <wan>"),
+                sp!("lon [ijo] li lon <lon> la ilo o toki e toki <tu>"),
+                sp!("ken la lon <lon> en lon <ala> li lon <ala>"),
+                sp!("lon ni li lon <lon> la ilo o pini linja"),
+            ],
+            &[
+                sp!("ilo o toki e toki <wan>"),
+                sp!("lon [ijo] li lon <lon> la ilo o toki e toki <tu>"),
+                sp!("o sona e lon <lon>"),
+                sp!("ilo o pini linja"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        check(
+            &[
+                sp!("nanpa <wan> li nanpa <tu> la o pali"),
+                sp!("ilo o toki e toki <wan>"),
+                sp!("ala la lon [ijo] li lon <lon> la o pali"),
+                sp!("ilo o toki e toki <tu>"),
+                sp!("ala la o pali"),
+                sp!("ilo o toki e toki <mute>"),
+                sp!("pini"),
+                sp!("toki <a> li toki <a> la o pali"),
+                sp!("o sona e nanpa <wan>"),
+                sp!("ala la o pali"),
+                sp!("o sona e nanpa <tu>"),
+                sp!("pini"),
+                sp!("ilo o toki e nanpa ni"),
+            ],
+            &[
+                sp!("lon [ijo] li lon <lon> la o pali"),
+                sp!("ilo o toki e toki <tu>"),
+                sp!("ala la o pali"),
+                sp!("ilo o toki e toki <mute>"),
+                sp!("pini"),
+                sp!("o pali"),
+                sp!("o sona e nanpa <wan>"),
+                sp!("pini"),
+                sp!("ilo o toki e nanpa <wan>"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        check(
+            &[
+                sp!("o sona e nanpa <ala>"),
+                sp!("nanpa ni li suli tawa nanpa <wan> la o sike"),
+                sp!("ilo o pini linja"),
+                sp!("pini"),
+                sp!("lon <lon> li lon <lon> la o sike"),
+                sp!("o wan e nanpa ni e nanpa <wan>"),
+                sp!("sike o pini"),
+                sp!("ilo o pini linja"),
+                sp!("pini"),
+                sp!("ilo o toki e nanpa ni"),
+            ],
+            &[
+                sp!("o sona e nanpa <ala>"),
+                sp!("o sike"),
+                sp!("o wan e nanpa ni e nanpa <wan>"),
+                sp!("sike o pini"),
+                sp!("pini"),
+                sp!("ilo o toki e nanpa ni"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_same_behaviour() {
+        let source = include_str!("../../../examples/musi_nanpa.lipu");
+        let run = |optimized: bool| {
+            let mut program = parse_program(source).expect("program should parse");
+            if optimized {
+                program = optimize(program);
+            }
+            let mut output = Vec::new();
+            let mut runtime = Runtime::with_console(Streams {
+                input: &b""[..],
+                output: &mut output,
+            });
+            runtime.run(&program).expect("program should run");
+            output
+        };
+        assert_eq!(run(true), run(false));
+    }
+}
@@ -0,0 +1,313 @@
+//! Assembles lines into nested blocks.
+
+use crate::{
+    ast::{
+        block::{IfBlock, IfLadder, LoopBlock, Statement},
+        statement::{BlockStart, BlockType},
+    },
+    parse::{Span, error::ParseError, statement::Line},
+};
+
+const ERR_UNCLOSED: &str = "block is never closed with ['󱥐']";
+const ERR_UNOPENED: &str = "['󱥐'] does not close any block";
+const ERR_ORPHAN_CHAIN: &str = "['󱤂󱤡'] must follow a conditional ['󱥄󱥉'] block";
+const ERR_UNREACHABLE_CHAIN: &str =
+    "the previous block has no condition, so this block can never run";
+const ERR_CHAIN_LOOP: &str = "loops cannot be chained using ['󱤂󱤡']";
+const HELP_CHAIN_LOOP: &str = "put the loop inside a chained ['󱥄󱥉'] block instead";
+const ERR_LOOP_CONTROL: &str = "['󱥜󱥄󱥐'] and ['󱥜󱥄󱥝'] can only be used inside a loop";
+
+/// A block that has been opened, but not yet closed.
+struct OpenBlock<'a> {
+    /// The line that opened the current block.
+    span: Span<'a>,
+    /// The start of the current block.
+    start: BlockStart,
+    /// Earlier blocks of the same ladder, if this is a *pali* block.
+    chain: Vec<IfBlock>,
+    /// The statements in the current block.
+    contents: Vec<Statement>,
+    /// Whether the line that opened the current block failed to parse, so its start is a guess.
+    broken: bool,
+}
+impl<'a> OpenBlock<'a> {
+    fn new(span: Span<'a>, start: BlockStart) -> Self {
+        Self {
+            span,
+            start,
+            chain: Vec::new(),
+            contents: Vec::new(),
+            broken: false,
+        }
+    }
+
+    /// Closes this block, turning it into a statement.
+    fn close(mut self) -> Statement {
+        match self.start.block_type {
+            BlockType::Pali => {
+                self.chain.push(IfBlock {
+                    start: self.start,
+                    contents: self.contents,
+                });
+                Statement::IfLadder(IfLadder { blocks: self.chain })
+            }
+            BlockType::Sike => Statement::LoopBlock(LoopBlock {
+                start: self.start,
+                contents: self.contents,
+            }),
+        }
+    }
+}
+
+/// Assembles a sequence of lines into statements, nesting blocks as needed.
+///
+/// Mistakes in the block structure are all collected, instead of stopping at the first one.
+pub(super) fn build_blocks<'a>(
+    lines: impl IntoIterator<Item = (Span<'a>, Line)>,
+) -> Result<Vec<Statement>, Vec<ParseError<'a>>> {
+    let mut root = Vec::new();
+    let mut stack: Vec<OpenBlock> = Vec::new();
+    let mut errors = Vec::new();
+
+    for (span, line) in lines {
+        match line {
+            Line::Statement(Statement::LoopControl(_))
+                if !stack.iter().any(|b| b.start.block_type == BlockType::Sike) =>
+            {
+                errors.push(ParseError::new(span, ERR_LOOP_CONTROL));
+            }
+            Line::Statement(stmt) => match stack.last_mut() {
+                Some(block) => block.contents.push(stmt),
+                None => root.push(stmt),
+            },
+            Line::BlockStart(start) if !start.chained => stack.push(OpenBlock::new(span, start)),
+            Line::BlockStart(start) => {
+                let error = match stack.last() {
+                    _ if start.block_type == BlockType::Sike => {
+                        Some(ParseError::new(span, ERR_CHAIN_LOOP).with_help(HELP_CHAIN_LOOP))
+                    }
+                    Some(block) if block.start.block_type != BlockType::Pali => {
+                        Some(ParseError::new(span, ERR_ORPHAN_CHAIN))
+                    }
+                    None => Some(ParseError::new(span, ERR_ORPHAN_CHAIN)),
+                    Some(block) if block.start.condition.is_none() && !block.broken => {
+                        Some(ParseError::new(span, ERR_UNREACHABLE_CHAIN))
+                    }
+                    Some(_) => None,
+                };
+                let broken = error.is_some();
+                errors.extend(error);
+                chain_block(&mut stack, span, start, broken);
+            }
+            Line::Invalid(Some(start)) if !start.chained => {
+                let mut block = OpenBlock::new(span, start);
+                block.broken = true;
+                stack.push(block);
+            }
+            Line::Invalid(Some(start)) => chain_block(&mut stack, span, start, true),
+            Line::Invalid(None) => (),
+            Line::BlockEnd => {
+                let Some(block) = stack.pop() else {
+                    errors.push(ParseError::new(span, ERR_UNOPENED));
+                    continue;
+                };
+                let stmt = block.close();
+                match stack.last_mut() {
+                    Some(parent) => parent.contents.push(stmt),
+                    None => root.push(stmt),
+                }
+            }
+        }
+    }
+
+    // report the outermost unclosed block first
+    errors.extend(
+        stack
+            .into_iter()
+            .map(|block| ParseError::new(block.span, ERR_UNCLOSED)),
+    );
+    match errors.is_empty() {
+        true => Ok(root),
+        false => Err(errors),
+    }
+}
+
+/// Moves the innermost block on to the next block of its ladder. If there is no block to chain
+/// to, a new one is opened so that the matching *pini* still lines up.
+fn chain_block<'a>(
+    stack: &mut Vec<OpenBlock<'a>>,
+    span: Span<'a>,
+    start: BlockStart,
+    broken: bool,
+) {
+    let Some(block) = stack.last_mut() else {
+        let mut block = OpenBlock::new(span, start);
+        block.broken = broken;
+        stack.push(block);
+        return;
+    };
+
+    let prev_start = std::mem::replace(&mut block.start, start);
+    let prev_contents = std::mem::take(&mut block.contents);
+    block.chain.push(IfBlock {
+        start: prev_start,
+        contents: prev_contents,
+    });
+    block.span = span;
+    block.broken = broken;
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::{block::Statement, statement::BlockType},
+        parse::{
+            Span,
+            block::build_blocks,
+            statement::{Line, line},
+        },
+    };
+
+    fn parse_lines(lines: &[&'static str]) -> Vec<(Span<'static>, Line)> {
+        lines
+            .iter()
+            .map(|&text| {
+                let span = Span::new(text);
+                let (_, line) = line(span).expect("line should parse");
+                (span, line)
+            })
+            .collect()
+    }
+    fn check_valid(lines: &[&'static str]) -> Vec<Statement> {
+        build_blocks(parse_lines(lines)).expect("blocks should build")
+    }
+    fn check_invalid(lines: &[&'static str], err_line: &str) {
+        let errors = build_blocks(parse_lines(lines)).expect_err("blocks should fail to build");
+        assert_eq!(*errors[0].span().fragment(), err_line);
+    }
+
+    #[test]
+    fn test_flat() {
+        let stmts = check_valid(&[sp!("ilo o pini linja"), sp!("ken la lon ni li lon <lon>")]);
+        assert!(matches!(
+            &stmts[..],
+            [Statement::Command(_), Statement::ConditionEval(_)]
+        ));
+    }
+
+    #[test]
+    fn test_nested() {
+        let stmts = check_valid(&[
+            sp!("nanpa [jan] li lili tawa nanpa <ale> la o sike"),
+            sp!("o pali"),
+            sp!("sike o sin"),
+            sp!("pini"),
+            sp!("ilo o pini linja"),
+            sp!("pini"),
+        ]);
+        let [Statement::LoopBlock(loop_block)] = &stmts[..] else {
+            panic!("expected a single loop, got {:?}", stmts);
+        };
+        assert!(loop_block.start.condition.is_some());
+        let [Statement::IfLadder(ladder), Statement::Command(_)] = &loop_block.contents[..] else {
+            panic!("unexpected loop contents {:?}", loop_block.contents);
+        };
+        assert_eq!(ladder.blocks.len(), 1);
+        assert!(ladder.blocks[0].start.condition.is_none());
+        assert!(matches!(
+            &ladder.blocks[0].contents[..],
+            [Statement::LoopControl(_)]
+        ));
+    }
+
+    #[test]
+    fn test_ladder() {
+        let stmts = check_valid(&[
+            sp!("lon [jan] li lon <lon> la o pali"),
+            sp!("ilo o pini linja"),
+            sp!("ala la lon [ijo] li lon <lon> la o pali"),
+            sp!("ala la o pali"),
+            sp!("ilo o pini linja"),
+            sp!("ilo o pini linja"),
+            sp!("pini"),
+        ]);
+        let [Statement::IfLadder(ladder)] = &stmts[..] else {
+            panic!("expected a single ladder, got {:?}", stmts);
+        };
+        let chained: Vec<_> = ladder.blocks.iter().map(|b| b.start.chained).collect();
+        assert_eq!(chained, [false, true, true]);
+        let lens: Vec<_> = ladder.blocks.iter().map(|b| b.contents.len()).collect();
+        assert_eq!(lens, [1, 0, 2]);
+        assert!(
+            ladder
+                .blocks
+                .iter()
+                .all(|b| b.start.block_type == BlockType::Pali)
+        );
+    }
+
+    #[test]
+    fn test_failures() {
+        check_invalid(
+            &[
+                sp!("o sike"),
+                sp!("o pali"),
+                sp!("pini"),
+                sp!("ilo o pini linja"),
+            ],
+            sp!("o sike"),
+        );
+        check_invalid(&[sp!("o pali"), sp!("pini"), sp!("pini")], sp!("pini"));
+        check_invalid(&[sp!("ala la o pali"), sp!("pini")], sp!("ala la o pali"));
+        check_invalid(
+            &[sp!("o sike"), sp!("ala la o pali"), sp!("pini")],
+            sp!("ala la o pali"),
+        );
+        check_invalid(
+            &[sp!("o pali"), sp!("ala la o pali"), sp!("pini")],
+            sp!("ala la o pali"),
+        );
+        check_invalid(
+            &[
+                sp!("lon ni li lon <lon> la o pali"),
+                sp!("ala la o sike"),
+                sp!("pini"),
+            ],
+            sp!("ala la o sike"),
+        );
+        check_invalid(
+            &[sp!("o pali"), sp!("sike o pini"), sp!("pini")],
+            sp!("sike o pini"),
+        );
+
+        let lines = parse_lines(&[
+            sp!("pini"),
+            sp!("sike o pini"),
+            sp!("o sike"),
+            sp!("o pali"),
+        ]);
+        let errors = build_blocks(lines).expect_err("blocks should fail to build");
+        let fragments: Vec<_> = errors.iter().map(|err| *err.span().fragment()).collect();
+        assert_eq!(
+            fragments,
+            [
+                sp!("pini"),
+                sp!("sike o pini"),
+                sp!("o sike"),
+                sp!("o pali")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_failures() {
+        let span = Span::new(sp!("lon ni li lon <lon> la pini"));
+        assert!(matches!(line(span), Err(nom::Err::Failure(_))));
+        let span = Span::new(sp!("ala la"));
+        assert!(matches!(line(span), Err(nom::Err::Failure(_))));
+        let span = Span::new(sp!("lon ni li lon <lon> o pali"));
+        assert!(matches!(line(span), Err(nom::Err::Failure(_))));
+    }
+}
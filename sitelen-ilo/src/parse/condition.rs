@@ -0,0 +1,330 @@
+use nom::{
+    Input, Parser, branch::alt, character::complete::char, combinator::value, sequence::pair,
+};
+use sitelen_ilo_macros::sp_c;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        condition::{CondAndEqual, CondEqual, CondGreater, CondLess, CondOrEqual, Condition},
+        object::Object,
+    },
+    parse::{
+        Span,
+        error::{ParseError, ParseResult, nom_force_failure},
+        object::object,
+    },
+};
+
+const ERR_MISS_OBJECT: &str = "expected an object in condition";
+const ERR_MISS_LI: &str = "missing ['󱤧'] in condition";
+const ERR_MIXED_CHAIN: &str = "['󱤊'] and ['󱤇'] cannot be mixed in one condition";
+const HELP_MIXED_CHAIN: &str =
+    "evaluate part of the condition to ni first using ['󱤘󱤡'], then use ['󱤬󱥁'] in its place";
+const ERR_CHAIN_COMPARE: &str = "['󱤊'] and ['󱤇'] chains may only be compared with ['󱤧']";
+
+/// Kinds of comparison that can follow *li*.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    Greater,
+    Less,
+}
+
+/// Parses the comparison after *li*.
+fn comparison(input: Span) -> ParseResult<Comparison> {
+    let (input1, _) = char(sp_c!("li"))(input)?;
+    let (input2, cmp) = alt((
+        value(
+            Comparison::Greater,
+            pair(char(sp_c!("suli")), char(sp_c!("tawa"))),
+        ),
+        value(
+            Comparison::Less,
+            pair(char(sp_c!("lili")), char(sp_c!("tawa"))),
+        ),
+        value(Comparison::Equal, |input| Ok((input, ()))),
+    ))
+    .parse_complete(input1)?;
+    Ok((input2, cmp))
+}
+
+/// Parses an object within a condition, along with its span.
+fn spanned_object(input: Span) -> ParseResult<(Object, SrcSpan)> {
+    let (input1, obj) = object(input)?;
+    Ok((input1, (obj, SrcSpan::between(input, input1))))
+}
+
+/// Parses an object within a condition, where one is required.
+fn required_object(input: Span) -> ParseResult<(Object, SrcSpan)> {
+    spanned_object(input).map_err(|err| match err {
+        nom::Err::Error(_) => ParseError::new(input, ERR_MISS_OBJECT).into_failure(),
+        _ => err,
+    })
+}
+
+/// The objects of an *en* or *anu* chain, with their spans.
+type Chain = (Vec<Object>, Vec<SrcSpan>, Object, SrcSpan);
+
+/// Parses the rest of an *en* or *anu* chain, after the first object.
+fn chain(
+    input: Span,
+    first: (Object, SrcSpan),
+    joiner: char,
+    other: char,
+) -> ParseResult<Chain> {
+    let (mut ps, mut p_spans) = (vec![first.0], vec![first.1]);
+
+    let mut remain = input;
+    while let Ok((remain1, _)) = char::<_, ParseError>(joiner).parse_complete(remain) {
+        let (remain2, (p, p_span)) = required_object(remain1)?;
+        ps.push(p);
+        p_spans.push(p_span);
+        remain = remain2;
+    }
+    if remain.starts_with(other) {
+        return Err(
+            ParseError::new(remain.take(other.len_utf8()), ERR_MIXED_CHAIN)
+                .with_help(HELP_MIXED_CHAIN)
+                .into_failure(),
+        );
+    }
+
+    let (remain1, cmp) = comparison(remain)
+        .map_err(ParseError::override_reason(ERR_MISS_LI))
+        .map_err(nom_force_failure)?;
+    if cmp != Comparison::Equal {
+        return Err(ParseError::new(remain, ERR_CHAIN_COMPARE).into_failure());
+    }
+    let (remain2, (q, q_span)) = required_object(remain1)?;
+
+    Ok((remain2, (ps, p_spans, q, q_span)))
+}
+
+/// Parses a condition.
+pub(super) fn condition(input: Span) -> ParseResult<Condition> {
+    let (input1, (a, a_span)) = spanned_object(input)?;
+
+    // li
+    if let Ok((input2, cmp)) = comparison(input1) {
+        let (input3, (b, b_span)) = required_object(input2)?;
+        let cond = match cmp {
+            Comparison::Equal => Condition::Equal(CondEqual {
+                a,
+                b,
+                a_span,
+                b_span,
+            }),
+            Comparison::Greater => Condition::Greater(CondGreater {
+                a,
+                b,
+                a_span,
+                b_span,
+            }),
+            Comparison::Less => Condition::Less(CondLess {
+                a,
+                b,
+                a_span,
+                b_span,
+            }),
+        };
+        return Ok((input3, cond));
+    }
+
+    // en/anu
+    let first = (a, a_span);
+    match input1.chars().next() {
+        Some(sp_c!("en")) => {
+            let (input2, (ps, p_spans, q, q_span)) =
+                chain(input1, first, sp_c!("en"), sp_c!("anu"))?;
+            let cond = CondAndEqual {
+                ps,
+                q,
+                p_spans,
+                q_span,
+            };
+            Ok((input2, Condition::AndEqual(cond)))
+        }
+        Some(sp_c!("anu")) => {
+            let (input2, (ps, p_spans, q, q_span)) =
+                chain(input1, first, sp_c!("anu"), sp_c!("en"))?;
+            let cond = CondOrEqual {
+                ps,
+                q,
+                p_spans,
+                q_span,
+            };
+            Ok((input2, Condition::OrEqual(cond)))
+        }
+        // the object isn't followed by anything that would make it a condition
+        _ => Err(ParseError::new(input1, ERR_MISS_LI).into_error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::{
+            SrcSpan,
+            condition::{CondAndEqual, CondEqual, CondGreater, CondLess, CondOrEqual, Condition},
+            object::{Literal, NiRef, Object, PrimitiveType, Variable},
+        },
+        parse::{Span, condition::condition},
+    };
+
+    fn check_valid(test_val: &str, val: Condition) {
+        let mut span: Span = Span::new(test_val);
+
+        let value: Condition;
+        (span, value) = condition(span).expect("parser should not error");
+
+        assert_eq!(value, val);
+        assert!(span.is_empty());
+    }
+    fn check_invalid(test_val: &str) {
+        let span: Span = Span::new(test_val);
+        let err = condition(span).expect_err("parser should fail");
+        assert!(!err.is_incomplete());
+    }
+    fn check_failure(test_val: &str) {
+        let span: Span = Span::new(test_val);
+        let err = condition(span).expect_err("parser should fail");
+        assert!(matches!(err, nom::Err::Failure(_)));
+    }
+
+    fn var(ptype: PrimitiveType, name: &str) -> Object {
+        Object::Variable(Variable {
+            ptype,
+            name: name.into(),
+        })
+    }
+    /// Finds the spans of objects written in order in a condition.
+    fn spans(test_val: &str, objects: &[&str]) -> Vec<SrcSpan> {
+        let mut offset = 0;
+        objects
+            .iter()
+            .map(|obj| {
+                let start = offset + test_val[offset..].find(obj).expect("object should be found");
+                offset = start + obj.len();
+                SrcSpan {
+                    offset: start,
+                    len: obj.len(),
+                }
+            })
+            .collect()
+    }
+    fn lon(value: bool) -> Object {
+        Object::Literal(Literal::Lon(value))
+    }
+
+    #[test]
+    fn test_equal() {
+        let test_val = sp!("toki [nimi] li toki <jan>");
+        let sp = spans(test_val, &[sp!("toki [nimi]"), sp!("toki <jan>")]);
+        check_valid(
+            test_val,
+            Condition::Equal(CondEqual {
+                a: var(PrimitiveType::Toki, sp!("nimi")),
+                b: Object::Literal(Literal::Toki(sp!("jan").into())),
+                a_span: sp[0],
+                b_span: sp[1],
+            }),
+        );
+        let test_val = sp!("nanpa ni li nanpa <ala>");
+        let sp = spans(test_val, &[sp!("nanpa ni"), sp!("nanpa <ala>")]);
+        check_valid(
+            test_val,
+            Condition::Equal(CondEqual {
+                a: Object::Ni(NiRef {
+                    ptype: PrimitiveType::Nanpa,
+                }),
+                b: Object::Literal(Literal::Nanpa(0)),
+                a_span: sp[0],
+                b_span: sp[1],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let test_val = sp!("nanpa [jan] li suli tawa nanpa [ijo]");
+        let sp = spans(test_val, &[sp!("nanpa [jan]"), sp!("nanpa [ijo]")]);
+        check_valid(
+            test_val,
+            Condition::Greater(CondGreater {
+                a: var(PrimitiveType::Nanpa, sp!("jan")),
+                b: var(PrimitiveType::Nanpa, sp!("ijo")),
+                a_span: sp[0],
+                b_span: sp[1],
+            }),
+        );
+        let test_val = sp!("nanpa [jan] li lili tawa nanpa <ale>");
+        let sp = spans(test_val, &[sp!("nanpa [jan]"), sp!("nanpa <ale>")]);
+        check_valid(
+            test_val,
+            Condition::Less(CondLess {
+                a: var(PrimitiveType::Nanpa, sp!("jan")),
+                b: Object::Literal(Literal::Nanpa(100)),
+                a_span: sp[0],
+                b_span: sp[1],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_chains() {
+        let test_val = sp!("lon [jan] en lon [ijo] li lon <lon>");
+        let sp = spans(test_val, &[sp!("lon [jan]"), sp!("lon [ijo]"), sp!("lon <lon>")]);
+        check_valid(
+            test_val,
+            Condition::AndEqual(CondAndEqual {
+                ps: vec![
+                    var(PrimitiveType::Lon, sp!("jan")),
+                    var(PrimitiveType::Lon, sp!("ijo")),
+                ],
+                q: lon(true),
+                p_spans: sp[..2].to_vec(),
+                q_span: sp[2],
+            }),
+        );
+        let test_val = sp!("lon [jan] anu lon [ijo] anu lon ni anu lon <ala> li lon <ala>");
+        let objects = [
+            sp!("lon [jan]"),
+            sp!("lon [ijo]"),
+            sp!("lon ni"),
+            sp!("lon <ala>"),
+            sp!("lon <ala>"),
+        ];
+        let sp = spans(test_val, &objects);
+        check_valid(
+            test_val,
+            Condition::OrEqual(CondOrEqual {
+                ps: vec![
+                    var(PrimitiveType::Lon, sp!("jan")),
+                    var(PrimitiveType::Lon, sp!("ijo")),
+                    Object::Ni(NiRef {
+                        ptype: PrimitiveType::Lon,
+                    }),
+                    lon(false),
+                ],
+                q: lon(false),
+                p_spans: sp[..4].to_vec(),
+                q_span: sp[4],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_failures() {
+        check_invalid(sp!("lon [jan]"));
+        check_invalid(sp!("lon [jan] o sin"));
+        check_failure(sp!("lon [jan] li"));
+        check_failure(sp!("lon [jan] li suli tawa"));
+        check_failure(sp!("lon [jan] en lon [ijo]"));
+        check_failure(sp!("lon [jan] en lon [ijo] anu lon [kon] li lon <lon>"));
+        check_failure(sp!("lon [jan] anu lon [ijo] en lon [kon] li lon <lon>"));
+        check_failure(sp!("lon [jan] en lon [ijo] li lili tawa lon <lon>"));
+    }
+}
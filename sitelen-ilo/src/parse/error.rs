@@ -32,6 +32,36 @@ impl<'a> ParseError<'a> {
 
     }
 
+    /// Gets the location of this error.
+    pub(crate) fn span(&self) -> Span<'a> {
+        self.span
+    }
+
+    /// Gets the reason for this error.
+    pub(crate) fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Gets the help message for this error, if any.
+    pub(crate) fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Gets the error that caused this error, if any.
+    pub(crate) fn cause(&self) -> Option<&ParseError<'a>> {
+        self.cause.as_deref()
+    }
+
+    /// Moves this error and its causes to another source, using `locate` to move each span.
+    pub(crate) fn relocate<'b>(&self, locate: &impl Fn(Span<'a>) -> Span<'b>) -> ParseError<'b> {
+        ParseError {
+            span: locate(self.span),
+            reason: self.reason.clone(),
+            help: self.help.clone(),
+            cause: self.cause.as_ref().map(|cause| Box::new(cause.relocate(locate))),
+        }
+    }
+
     /// Adds a help message to this error.
     pub(crate) fn with_help(mut self, help: impl Into<String>) -> Self {
         self.help = Some(help.into());
@@ -0,0 +1,312 @@
+//! Splitting source code into classified tokens, for highlighting and semantic tokens.
+//!
+//! The parsers don't use these tokens: the lexer is a separate, simpler pass that classifies words
+//! mostly on their own, so it may disagree with the parsers on programs that don't parse. Unlike
+//! the parsers, it never fails: anything it doesn't recognise becomes an [`TokenKind::Unknown`]
+//! token, so that programs can be highlighted while they are being written.
+
+use nom::{
+    Input, Parser,
+    branch::alt,
+    bytes::complete::{is_not, tag, take_till, take_while1},
+    character::complete::{anychar, char},
+    combinator::{opt, recognize},
+    multi::many0,
+};
+use sitelen_ilo_macros::sp_c;
+
+use crate::{
+    ast::SrcSpan,
+    parse::{Span, error::ParseResult, next_line, statement::is_arg_key, util::CharSitelenPonaExt},
+};
+
+/// What kind of thing a token is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    /// A type word (*toki*, *nanpa* or *lon*) naming the type of an object.
+    Type,
+    /// A word that structures statements, such as *o*, *la* or *pini*, as well as *ilo* and *ni*.
+    Keyword,
+    /// A word that starts an argument, other than *e*.
+    Preposition,
+    /// A variable name in a cartouche, including the cartouche.
+    Identifier,
+    /// A *toki* or *lon* literal, or the quotes around a *nanpa* literal.
+    Literal,
+    /// A glyph in a *nanpa* literal.
+    Number,
+    /// The text of a comment, or a `#!` line.
+    Comment,
+    /// Any other word, such as the name of an action.
+    Word,
+    /// Text that is not part of the language.
+    Unknown,
+}
+impl TokenKind {
+    /// Gets the name of this kind, as used in highlighted output.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            TokenKind::Type => "type",
+            TokenKind::Keyword => "keyword",
+            TokenKind::Preposition => "preposition",
+            TokenKind::Identifier => "identifier",
+            TokenKind::Literal => "literal",
+            TokenKind::Number => "number",
+            TokenKind::Comment => "comment",
+            TokenKind::Word => "word",
+            TokenKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// A classified region of the source code. Tokens never span more than one line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) span: SrcSpan,
+}
+
+fn is_type_word(c: char) -> bool {
+    matches!(c, sp_c!("toki") | sp_c!("nanpa") | sp_c!("lon"))
+}
+
+/// Classifies a word on its own. Type words are only types when an object follows them.
+fn word_kind(c: char) -> TokenKind {
+    match c {
+        // ilo is the target of a command, and ni the value of the last expression
+        sp_c!("ilo")
+        | sp_c!("ni")
+        | sp_c!("e")
+        | sp_c!("o")
+        | sp_c!("la")
+        | sp_c!("pini")
+        | sp_c!("sike")
+        | sp_c!("pali")
+        | sp_c!("len")
+        | sp_c!("li")
+        | sp_c!("en")
+        | sp_c!("anu")
+        | sp_c!("ken")
+        | sp_c!("ala") => TokenKind::Keyword,
+        c if is_arg_key(c) => TokenKind::Preposition,
+        _ => TokenKind::Word,
+    }
+}
+
+/// Recognises a cartouche, which may be left open at the end of a line.
+fn cartouche(input: Span) -> ParseResult<Span> {
+    recognize((
+        char(sp_c!("[")),
+        take_till(|c| c == sp_c!("]")),
+        opt(char(sp_c!("]"))),
+    ))
+    .parse_complete(input)
+}
+
+/// Recognises a quoted literal with doubled quotes inside, which may be left open.
+fn quoted(input: Span) -> ParseResult<Span> {
+    recognize((
+        char('「'),
+        many0(alt((tag("「「"), tag("」」"), is_not("「」")))),
+        opt(char('」')),
+    ))
+    .parse_complete(input)
+}
+
+/// Collects tokens for one line at a time.
+struct Lexer {
+    tokens: Vec<Token>,
+}
+impl Lexer {
+    fn push(&mut self, kind: TokenKind, start: Span, end: Span) {
+        self.tokens.push(Token {
+            kind,
+            span: SrcSpan::between(start, end),
+        });
+    }
+
+    /// Splits a *nanpa* literal into its quotes and number glyphs.
+    fn nanpa_literal(&mut self, literal: Span) {
+        let (mut rest, _) = literal.take_split('「'.len_utf8());
+        self.push(TokenKind::Literal, literal, rest);
+        while let Ok((rest1, c)) = anychar::<_, nom::error::Error<Span>>(rest) {
+            let kind = match c {
+                '」' => TokenKind::Literal,
+                c if c.is_whitespace() => {
+                    rest = rest1;
+                    continue;
+                }
+                _ => TokenKind::Number,
+            };
+            self.push(kind, rest, rest1);
+            rest = rest1;
+        }
+    }
+
+    fn line(&mut self, input: Span) {
+        let text = input.take_from(input.len() - input.trim_start().len());
+        let text = text.take(text.trim_end().len());
+
+        // len: the rest of the line is a comment
+        if let Some(comment) = text.strip_prefix(sp_c!("len")) {
+            let (rest, _) = text.take_split(sp_c!("len").len_utf8());
+            self.push(TokenKind::Keyword, text, rest);
+            if !comment.trim().is_empty() {
+                let rest = rest.take_from(rest.len() - rest.trim_start().len());
+                self.push(TokenKind::Comment, rest, rest.take_from(rest.len()));
+            }
+            return;
+        }
+
+        let mut rest = text;
+        // the type word just before, which decides how a literal is split up
+        let mut last_type = None;
+        while let Ok((rest1, c)) = anychar::<_, nom::error::Error<Span>>(rest) {
+            if c.is_whitespace() {
+                rest = rest1;
+                continue;
+            }
+
+            let (rest1, kind) = if let Ok((rest1, _)) = cartouche(rest) {
+                (rest1, TokenKind::Identifier)
+            } else if let Ok((rest1, literal)) = quoted(rest) {
+                match last_type {
+                    Some(sp_c!("nanpa")) => self.nanpa_literal(literal),
+                    _ => self.push(TokenKind::Literal, rest, rest1),
+                }
+                rest = rest1;
+                last_type = None;
+                continue;
+            } else if is_type_word(c) {
+                // followed by a cartouche, a literal or ni
+                let next = rest1.trim_start().chars().next();
+                match next {
+                    Some(sp_c!("[") | '「' | sp_c!("ni")) => (rest1, TokenKind::Type),
+                    _ => (rest1, TokenKind::Word),
+                }
+            } else if c.is_sp_word() {
+                (rest1, word_kind(c))
+            } else {
+                // a run of anything else
+                let rest1 = take_while1::<_, _, nom::error::Error<Span>>(|c: char| {
+                    !c.is_whitespace() && !c.is_sp_word() && !matches!(c, '「' | sp_c!("["))
+                })
+                .parse_complete(rest)
+                .map_or(rest1, |(rest1, _)| rest1);
+                (rest1, TokenKind::Unknown)
+            };
+
+            last_type = match kind {
+                TokenKind::Type => Some(c),
+                _ => None,
+            };
+            self.push(kind, rest, rest1);
+            rest = rest1;
+        }
+    }
+}
+
+/// Splits a program into tokens, in the order they appear. Whitespace is left out.
+pub(crate) fn tokenize(source: &str) -> Vec<Token> {
+    let mut lexer = Lexer { tokens: Vec::new() };
+    let mut remain = Span::new(source);
+
+    if remain.starts_with("#!") {
+        let (remain1, shebang) = next_line(remain);
+        let shebang = shebang.take(shebang.trim_end().len());
+        lexer.push(
+            TokenKind::Comment,
+            shebang,
+            shebang.take_from(shebang.len()),
+        );
+        remain = remain1;
+    }
+    while !remain.is_empty() {
+        let text;
+        (remain, text) = next_line(remain);
+        lexer.line(text);
+    }
+    lexer.tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::parse::lex::{TokenKind, tokenize};
+
+    /// Tokenizes a program, returning the text and kind of each token.
+    fn check(source: &str, expected: &[(&str, TokenKind)]) {
+        let tokens: Vec<_> = tokenize(source)
+            .into_iter()
+            .map(|token| {
+                let text = &source[token.span.offset..token.span.offset + token.span.len];
+                (text, token.kind)
+            })
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_statements() {
+        use TokenKind::*;
+        check(
+            sp!("ilo o toki e toki <a<<> tawa nanpa [ijo]"),
+            &[
+                (sp!("ilo"), Keyword),
+                (sp!("o"), Keyword),
+                (sp!("toki"), Word),
+                (sp!("e"), Keyword),
+                (sp!("toki"), Type),
+                (sp!("<a<<>"), Literal),
+                (sp!("tawa"), Preposition),
+                (sp!("nanpa"), Type),
+                (sp!("[ijo]"), Identifier),
+            ],
+        );
+        check(
+            sp!("nanpa ni li suli tawa nanpa <mute weka>  la o pali"),
+            &[
+                (sp!("nanpa"), Type),
+                (sp!("ni"), Keyword),
+                (sp!("li"), Keyword),
+                (sp!("suli"), Word),
+                (sp!("tawa"), Preposition),
+                (sp!("nanpa"), Type),
+                ("「", Literal),
+                (sp!("mute"), Number),
+                (sp!("weka"), Number),
+                ("」", Literal),
+                (sp!("la"), Keyword),
+                (sp!("o"), Keyword),
+                (sp!("pali"), Keyword),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        use TokenKind::*;
+        let source = [
+            "#!/usr/bin/env o-ilo ",
+            &(sp!("len").to_string() + "\u{3000}toki pona "),
+            &("\t".to_string() + sp!("sike o pini")),
+            concat!(sp!("o"), " hello ", sp!("[ijo")),
+        ]
+        .join("\n");
+        check(
+            &source,
+            &[
+                ("#!/usr/bin/env o-ilo", Comment),
+                (sp!("len"), Keyword),
+                ("toki pona", Comment),
+                (sp!("sike"), Keyword),
+                (sp!("o"), Keyword),
+                (sp!("pini"), Keyword),
+                (sp!("o"), Keyword),
+                ("hello", Unknown),
+                (sp!("[ijo"), Identifier),
+            ],
+        );
+    }
+}
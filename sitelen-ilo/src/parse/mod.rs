@@ -1,9 +1,251 @@
-
+use nom::Input;
 use nom_locate::LocatedSpan;
+use sitelen_ilo_macros::sp;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        statement::{BlockStart, BlockType},
+    },
+    parse::{
+        block::build_blocks,
+        error::ParseError,
+        statement::{Line, line},
+        util::CharSitelenPonaExt,
+    },
+};
 
+pub(crate) mod block;
+pub(crate) mod condition;
 pub(crate) mod error;
+pub(crate) mod lex;
 pub(crate) mod object;
+pub(crate) mod statement;
 pub(crate) mod util;
 
 pub(crate) type Span<'a> = LocatedSpan<&'a str>;
 
+const ERR_COMBINING: &str = "combining characters are not allowed";
+const ERR_TRAILING: &str = "unexpected characters after the end of the statement";
+
+impl SrcSpan {
+    /// Gets the region from the start of `start` up to the start of `end`.
+    pub(crate) fn between(start: Span, end: Span) -> Self {
+        Self {
+            offset: start.location_offset(),
+            len: end.location_offset() - start.location_offset(),
+        }
+    }
+
+    /// Finds this region in the source code it was parsed from.
+    pub(crate) fn locate(self, source: &str) -> Span<'_> {
+        Span::new(source).take_from(self.offset).take(self.len)
+    }
+}
+
+/// Splits off the next line of the input, without its line break.
+fn next_line(input: Span) -> (Span, Span) {
+    let (input1, text) = input.take_split(input.find('\n').unwrap_or(input.len()));
+    match input1.is_empty() {
+        true => (input1, text),
+        false => (input1.take_from(1), text),
+    }
+}
+
+/// Parses one line, with its indentation and trailing whitespace already removed.
+fn program_line(input: Span) -> Result<Line, ParseError> {
+    if let Some(i) = input.find(|c: char| c.is_combining()) {
+        let c_len = input[i..].chars().next().unwrap().len_utf8();
+        return Err(ParseError::new(
+            input.take_from(i).take(c_len),
+            ERR_COMBINING,
+        ));
+    }
+
+    let (remain, line) = line(input).map_err(|err| match err {
+        nom::Err::Error(err) | nom::Err::Failure(err) => err,
+        nom::Err::Incomplete(_) => unreachable!("line parsers are complete"),
+    })?;
+    if !remain.is_empty() {
+        return Err(ParseError::new(remain, ERR_TRAILING));
+    }
+    Ok(line)
+}
+
+/// Guesses whether a line that failed to parse was meant to start a block.
+fn guess_block_start(text: Span) -> Option<BlockStart> {
+    let block_type = if text.ends_with(sp!("o pali")) {
+        BlockType::Pali
+    } else if text.ends_with(sp!("o sike")) {
+        BlockType::Sike
+    } else {
+        return None;
+    };
+    Some(BlockStart {
+        chained: text.starts_with(sp!("ala la")),
+        condition: None,
+        block_type,
+        span: SrcSpan::between(text, text.take_from(text.len())),
+    })
+}
+
+/// Finds how a line changes the nesting of blocks: 1 if it opens a block, -1 if it closes one,
+/// and 0 otherwise. Lines that don't parse are guessed at.
+pub(crate) fn block_depth_change(text: &str) -> isize {
+    let text = Span::new(text.trim());
+    let start = match program_line(text) {
+        Ok(Line::BlockStart(start)) => Some(start),
+        Ok(Line::BlockEnd) => return -1,
+        Ok(_) => None,
+        Err(_) => guess_block_start(text),
+    };
+    match start {
+        Some(start) if !start.chained => 1,
+        _ => 0,
+    }
+}
+
+/// Parses the contents of a *sitelen ilo* program.
+///
+/// Lines that fail to parse are skipped, so that every error in the program can be reported at
+/// once. The errors are sorted by where they occur.
+pub(crate) fn parse_program(input: &str) -> Result<Vec<Statement>, Vec<ParseError<'_>>> {
+    let mut remain = Span::new(input);
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+
+    // shebang
+    if remain.starts_with("#!") {
+        (remain, _) = next_line(remain);
+    }
+
+    while !remain.is_empty() {
+        let text;
+        (remain, text) = next_line(remain);
+
+        // indentation and trailing whitespace
+        let text = text.take_from(text.len() - text.trim_start().len());
+        let text = text.take(text.trim_end().len());
+        if text.is_empty() {
+            continue;
+        }
+
+        match program_line(text) {
+            Ok(line) => lines.push((text, line)),
+            Err(err) => {
+                errors.push(err);
+                lines.push((text, Line::Invalid(guess_block_start(text))));
+            }
+        }
+    }
+
+    match build_blocks(lines) {
+        Ok(stmts) if errors.is_empty() => Ok(stmts),
+        Ok(_) => Err(errors),
+        Err(block_errors) => {
+            errors.extend(block_errors);
+            errors.sort_by_key(|err| err.span().location_offset());
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::{SrcSpan, block::Statement, statement::Comment},
+        parse::parse_program,
+    };
+
+    #[test]
+    fn test_example() {
+        let program = include_str!("../../../examples/musi_nanpa.lipu");
+        let stmts = parse_program(program).expect("example should parse");
+        assert!(matches!(
+            &stmts[..],
+            [
+                Statement::Comment(_),
+                Statement::Comment(_),
+                Statement::Command(_),
+                Statement::LoopBlock(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_comments() {
+        let program = concat!("#!/usr/bin/env o-ilo\n", sp!("len"), "　toki pona\n");
+        let stmts = parse_program(program).expect("program should parse");
+        assert_eq!(
+            stmts,
+            [Statement::Comment(Comment {
+                content: "toki pona".into(),
+                span: SrcSpan {
+                    offset: 21,
+                    len: program.len() - 22
+                }
+            })]
+        );
+    }
+
+    #[test]
+    fn test_indentation() {
+        let program = concat!(
+            sp!("o pali"),
+            "\r\n\t",
+            sp!("ilo o pini linja"),
+            "  \n　　",
+            sp!("ilo o pini linja"),
+            "\n\n",
+            sp!("pini"),
+        );
+        let stmts = parse_program(program).expect("program should parse");
+        let [Statement::IfLadder(ladder)] = &stmts[..] else {
+            panic!("expected a single block, got {:?}", stmts);
+        };
+        assert_eq!(ladder.blocks[0].contents.len(), 2);
+    }
+
+    #[test]
+    fn test_failures() {
+        let program = concat!(sp!("ilo o pini linja"), "\n", sp!("ilo o pini"), "\u{301}");
+        let errors = parse_program(program).expect_err("combining characters should fail");
+        assert_eq!(*errors[0].span().fragment(), "\u{301}");
+        assert_eq!(errors[0].span().location_line(), 2);
+
+        for mark in ["\u{591}", "\u{93C}", "\u{20DD}", "\u{F1995}"] {
+            let program = format!("{}{}{}", sp!("ilo o toki e toki <"), mark, sp!(">"));
+            let errors = parse_program(&program).expect_err("combining characters should fail");
+            assert_eq!(*errors[0].span().fragment(), mark);
+        }
+
+        let program = concat!(
+            sp!("ilo o pini linja"),
+            "\n",
+            sp!("ken la lon ni li lon ni pona")
+        );
+        let errors = parse_program(program).expect_err("trailing characters should fail");
+        assert_eq!(*errors[0].span().fragment(), sp!("pona"));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let program = [
+            sp!("lon ni li lon <lon> o pali"),
+            sp!("ilo o pini linja"),
+            sp!("ala la"),
+            sp!("ala la lon ni li lon <lon> o pali"),
+            sp!("pini"),
+            sp!("sike o pini"),
+            sp!("ken la lon ni li lon ni pona"),
+            sp!("o sike"),
+        ]
+        .join("\n");
+        let errors = parse_program(&program).expect_err("program should fail");
+        let lines: Vec<_> = errors.iter().map(|err| err.span().location_line()).collect();
+        assert_eq!(lines, [1, 3, 4, 6, 7, 8]);
+    }
+}
@@ -1,18 +1,34 @@
 use nom::{branch::alt, character::char, combinator::value, Parser};
 use sitelen_ilo_macros::sp_c;
 
-use crate::{ast::object::Literal, parse::{error::{nom_force_failure, ParseError, ParseResult}, Span}};
+use crate::{ast::object::Literal, parse::{error::{nom_force_failure, ParseError, ParseResult}, object::ERR_MISS_CLOSE_QUOTE, Span}};
 
-const ERR_FAILED_LON_MATCH: &str = "`lon`-typed value may only take `lon` or `ala`";
+pub(crate) const ERR_FAILED_LON_MATCH: &str = "`lon`-typed value may only take `lon` or `ala`";
+
+/// Parses the word of a *lon* value.
+fn lon_word(input: Span) -> ParseResult<bool> {
+    alt([
+        value(true, char(sp_c!("lon"))),
+        value(false, char(sp_c!("ala"))),
+    ]).parse_complete(input).map_err(ParseError::override_reason(ERR_FAILED_LON_MATCH)).map_err(nom_force_failure)
+}
+
+/// Reads a whole string as a *lon* value, returning the reason if it isn't one.
+pub(crate) fn lon_from_text(text: &str) -> Result<bool, &'static str> {
+    match lon_word(Span::new(text)) {
+        Ok((remain, value)) if remain.is_empty() => Ok(value),
+        _ => Err(ERR_FAILED_LON_MATCH),
+    }
+}
 
 /// Parses the quoted portion of a *lon* literal.
 pub(super) fn lon_quoted(input: Span) -> ParseResult<Literal> {
     let (input1, _) = char('「').parse_complete(input)?;
-    let (input2, value) = alt([
-        value(true, char(sp_c!("lon"))),
-        value(false, char(sp_c!("ala"))),
-    ]).parse_complete(input1).map_err(ParseError::override_reason(ERR_FAILED_LON_MATCH)).map_err(nom_force_failure)?;
-    let (input3, _) = char('」').parse_complete(input2).map_err(nom_force_failure)?;
+    let (input2, value) = lon_word(input1)?;
+    let (input3, _) = char('」')
+        .parse_complete(input2)
+        .map_err(ParseError::override_reason(ERR_MISS_CLOSE_QUOTE))
+        .map_err(nom_force_failure)?;
     Ok((input3, Literal::Lon(value)))
 }
 
@@ -20,7 +36,7 @@ pub(super) fn lon_quoted(input: Span) -> ParseResult<Literal> {
 mod tests {
     use sitelen_ilo_macros::sp;
 
-    use crate::{ast::object::Literal, parse::{object::lon::lon_quoted, Span}};
+    use crate::{ast::object::Literal, parse::{object::lon::{lon_from_text, lon_quoted, ERR_FAILED_LON_MATCH}, Span}};
 
     fn check_valid(test_val: &str, val: bool) {
         let mut span: Span = Span::new(test_val);
@@ -53,4 +69,12 @@ mod tests {
         check_invalid(sp!("<lon suli>"));
         check_invalid(sp!("<o moli e mi>"));
     }
+
+    #[test]
+    fn test_from_text() {
+        assert_eq!(lon_from_text(sp!("lon")), Ok(true));
+        assert_eq!(lon_from_text(sp!("ala")), Ok(false));
+        assert_eq!(lon_from_text(sp!("lon suli")), Err(ERR_FAILED_LON_MATCH));
+        assert_eq!(lon_from_text(""), Err(ERR_FAILED_LON_MATCH));
+    }
 }
\ No newline at end of file
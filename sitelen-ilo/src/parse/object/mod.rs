@@ -21,6 +21,9 @@ mod lon;
 mod nanpa;
 mod toki;
 
+pub(crate) use lon::{ERR_FAILED_LON_MATCH, lon_from_text};
+pub(crate) use nanpa::{ERR_NNP_FAILED_MATCH, ERR_NNP_TOO_LARGE, nanpa_from_text, nanpa_to_text};
+
 const ERR_MISS_OPEN_QUOTE: &str = "missing opening quote ['「'] for literal";
 const ERR_MISS_CLOSE_QUOTE: &str = "missing closing quote ['」'] for literal";
 
@@ -49,7 +52,9 @@ pub(super) fn cartouche(input: Span) -> ParseResult<String> {
             .map_err(ParseError::override_reason(ERR_VAR_FORMAT))
             .map_err(nom_force_failure)?;
     // cartouche close
-    let (input4, _) = char(sp_c!("]"))(input3).map_err(nom_force_failure)?;
+    let (input4, _) = char(sp_c!("]"))(input3)
+        .map_err(ParseError::override_reason(ERR_MISS_CLOSE_CART))
+        .map_err(nom_force_failure)?;
 
     let value = format!("{}{}", c0, rest);
     Ok((input4, value))
@@ -74,11 +79,16 @@ pub(super) fn object(input: Span) -> ParseResult<Object> {
             PrimitiveType::Nanpa => nanpa_quoted,
             PrimitiveType::Lon => lon_quoted,
         },
-        |lit| Object::Literal(lit),
+        Object::Literal,
     );
 
     // parse for the corresponding type
-    let (input2, out) = alt((parse_ni, parse_cartouche, parse_quoted)).parse(input1)?;
+    let (input2, out) = alt((parse_ni, parse_cartouche, parse_quoted))
+        .parse(input1)
+        .map_err(|err| match err {
+            nom::Err::Error(_) => ParseError::new(input1, ERR_MISS_OPEN_QUOTE).into_error(),
+            err => err,
+        })?;
 
     Ok((input2, out))
 }
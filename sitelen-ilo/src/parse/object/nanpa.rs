@@ -4,16 +4,19 @@ use nom::{Input, Parser, character::char};
 use sitelen_ilo_macros::sp_c;
 
 use crate::{ast::object::Literal, parse::{
-    error::{nom_force_failure, ParseError, ParseResult}, Span
+    error::{nom_force_failure, ParseError, ParseResult}, object::ERR_MISS_CLOSE_QUOTE, Span
 }};
 
-const ERR_NNP_TOO_LARGE: &str = "value out of range for nanpa";
-const ERR_NNP_FAILED_MATCH: &str = "failed to match nasin nanpa pona number";
+pub(crate) const ERR_NNP_TOO_LARGE: &str = "value out of range for nanpa";
+pub(crate) const ERR_NNP_FAILED_MATCH: &str = "failed to match nasin nanpa pona number";
 
 /// Parses [*nasin nanpa pona*](https://sona.pona.la/wiki/nasin_nanpa_pona), the de-facto standard
 /// for writing large numbers quickly in *toki pona*.
-fn nasin_nanpa_pona(input: Span) -> ParseResult<i64> {
-    let mut result = 0i64;
+///
+/// Returns the number of bytes read along with the value, or the reason for failing.
+fn nasin_nanpa_pona_prefix(input: &str) -> Result<(usize, i64), &'static str> {
+    // wider than nanpa, so that -(2^63) can be negated from its magnitude
+    let mut result = 0i128;
     let mut expect_end = false;
     let mut add_ale = false;
     let mut end_idx: Option<usize> = None;
@@ -28,7 +31,7 @@ fn nasin_nanpa_pona(input: Span) -> ParseResult<i64> {
                 }
                 sp_c!("weka") => {
                     // special case: weka can't be first
-                    return Err(ParseError::new(input, ERR_NNP_FAILED_MATCH).into_failure());
+                    return Err(ERR_NNP_FAILED_MATCH);
                 }
                 _ => ()
             }
@@ -45,34 +48,22 @@ fn nasin_nanpa_pona(input: Span) -> ParseResult<i64> {
             sp_c!("ale") => {
                 // check if ale was the first character
                 if add_ale {
-                    result = result
-                        .checked_add(100)
-                        .ok_or(ParseError::new(input, ERR_NNP_TOO_LARGE).into_failure())?
+                    result = result.checked_add(100).ok_or(ERR_NNP_TOO_LARGE)?
                 } else {
-                    result = result
-                        .checked_mul(100)
-                        .ok_or(ParseError::new(input, ERR_NNP_TOO_LARGE).into_failure())?
+                    result = result.checked_mul(100).ok_or(ERR_NNP_TOO_LARGE)?
                 }
             }
             sp_c!("mute") => {
-                result = result
-                    .checked_add(20)
-                    .ok_or(ParseError::new(input, ERR_NNP_TOO_LARGE).into_failure())?;
+                result = result.checked_add(20).ok_or(ERR_NNP_TOO_LARGE)?;
             }
             sp_c!("luka") => {
-                result = result
-                    .checked_add(5)
-                    .ok_or(ParseError::new(input, ERR_NNP_TOO_LARGE).into_failure())?;
+                result = result.checked_add(5).ok_or(ERR_NNP_TOO_LARGE)?;
             }
             sp_c!("tu") => {
-                result = result
-                    .checked_add(2)
-                    .ok_or(ParseError::new(input, ERR_NNP_TOO_LARGE).into_failure())?;
+                result = result.checked_add(2).ok_or(ERR_NNP_TOO_LARGE)?;
             }
             sp_c!("wan") => {
-                result = result
-                    .checked_add(1)
-                    .ok_or(ParseError::new(input, ERR_NNP_TOO_LARGE).into_failure())?;
+                result = result.checked_add(1).ok_or(ERR_NNP_TOO_LARGE)?;
             }
             sp_c!("weka") => {
                 // number will end immediately after weka
@@ -86,30 +77,84 @@ fn nasin_nanpa_pona(input: Span) -> ParseResult<i64> {
         }
     }
 
-    let remain = match end_idx {
-        Some(0) => {
-            return Err(ParseError::new(input, ERR_NNP_FAILED_MATCH).into_failure());
-        }
-        Some(i) => input.take_from(i),
-        None => input.take_from(input.len()),
-    };
+    let result = i64::try_from(result).map_err(|_| ERR_NNP_TOO_LARGE)?;
+    match end_idx {
+        Some(0) => Err(ERR_NNP_FAILED_MATCH),
+        Some(i) => Ok((i, result)),
+        None if input.is_empty() => Err(ERR_NNP_FAILED_MATCH),
+        None => Ok((input.len(), result)),
+    }
+}
+
+/// Parses [*nasin nanpa pona*](https://sona.pona.la/wiki/nasin_nanpa_pona) from the input.
+fn nasin_nanpa_pona(input: Span) -> ParseResult<i64> {
+    match nasin_nanpa_pona_prefix(input.fragment()) {
+        Ok((len, value)) => Ok((input.take_from(len), value)),
+        Err(reason) => Err(ParseError::new(input, reason).into_failure()),
+    }
+}
+
+/// Reads a whole string as [*nasin nanpa pona*](https://sona.pona.la/wiki/nasin_nanpa_pona),
+/// returning the reason if it isn't a valid number.
+pub(crate) fn nanpa_from_text(text: &str) -> Result<i64, &'static str> {
+    match nasin_nanpa_pona_prefix(text)? {
+        (len, value) if len == text.len() => Ok(value),
+        _ => Err(ERR_NNP_FAILED_MATCH),
+    }
+}
+
+/// Writes a value in canonical [*nasin nanpa pona*](https://sona.pona.la/wiki/nasin_nanpa_pona),
+/// using one *ale* between each group of two decimal digits.
+pub(crate) fn nanpa_to_text(value: i64) -> String {
+    if value == 0 {
+        return sp_c!("ala").to_string();
+    }
+
+    let mut groups = Vec::new();
+    let mut magnitude = value.unsigned_abs();
+    while magnitude > 0 {
+        groups.push(magnitude % 100);
+        magnitude /= 100;
+    }
 
-    Ok((remain, result))
+    let mut result = String::new();
+    for (i, &group) in groups.iter().rev().enumerate() {
+        if i > 0 {
+            result.push(sp_c!("ale"));
+        }
+        let digits = [
+            (sp_c!("mute"), group / 20),
+            (sp_c!("luka"), group % 20 / 5),
+            (sp_c!("tu"), group % 5 / 2),
+            (sp_c!("wan"), group % 5 % 2),
+        ];
+        for (c, count) in digits {
+            result.extend(std::iter::repeat_n(c, count as usize));
+        }
+    }
+    if value < 0 {
+        result.push(sp_c!("weka"));
+    }
+    result
 }
 
 /// Parses the quoted portion of a *nanpa* literal.
 pub(super) fn nanpa_quoted(input: Span) -> ParseResult<Literal> {
     let (input1, _) = char('「').parse_complete(input)?;
     let (input2, value) = nasin_nanpa_pona(input1)?;
-    let (input3, _) = char('」').parse_complete(input2).map_err(nom_force_failure)?;
+    let (input3, _) = char('」')
+        .parse_complete(input2)
+        .map_err(ParseError::override_reason(ERR_MISS_CLOSE_QUOTE))
+        .map_err(nom_force_failure)?;
     Ok((input3, Literal::Nanpa(value)))
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
     use sitelen_ilo_macros::sp;
 
-    use crate::{ast::object::Literal, parse::{object::nanpa::nanpa_quoted, Span}};
+    use crate::{ast::object::Literal, parse::{object::nanpa::{nanpa_from_text, nanpa_quoted, nanpa_to_text, ERR_NNP_FAILED_MATCH, ERR_NNP_TOO_LARGE}, Span}};
 
     fn check_valid(test_val: &str, num: i64) {
         let mut span: Span = Span::new(test_val);
@@ -201,5 +246,35 @@ mod tests {
         check_invalid(sp!("<ala weka>"));
         check_invalid(sp!("<wan weka suli a>"));
         check_invalid(sp!("<weka>"));
+        check_invalid(sp!("<>"));
+    }
+
+    #[test]
+    fn test_from_text() {
+        assert_eq!(nanpa_from_text(sp!("mute tu")), Ok(22));
+        assert_eq!(nanpa_from_text(sp!("ala")), Ok(0));
+        assert_eq!(nanpa_from_text(sp!("wan suli")), Err(ERR_NNP_FAILED_MATCH));
+        assert_eq!(nanpa_from_text(""), Err(ERR_NNP_FAILED_MATCH));
+        assert_eq!(
+            nanpa_from_text(sp!("wan ale ale ale ale ale ale ale ale ale ale")),
+            Err(ERR_NNP_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn test_to_text() {
+        assert_eq!(nanpa_to_text(0), sp!("ala"));
+        assert_eq!(nanpa_to_text(100), sp!("wan ale"));
+        assert_eq!(nanpa_to_text(1984), sp!("luka luka luka tu tu ale mute mute mute mute tu tu"));
+        assert_eq!(nanpa_to_text(-10005), sp!("wan ale ale luka weka"));
+        assert_eq!(nanpa_from_text(&nanpa_to_text(i64::MIN)), Ok(i64::MIN));
+        assert_eq!(nanpa_from_text(&nanpa_to_text(i64::MAX)), Ok(i64::MAX));
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(value: i64) {
+            prop_assert_eq!(nanpa_from_text(&nanpa_to_text(value)), Ok(value));
+        }
     }
 }
\ No newline at end of file
@@ -40,8 +40,8 @@ pub(super) fn toki_quoted(input: Span) -> ParseResult<Literal> {
             alt((tag("「「"), tag("」」"), tag("「"), tag("」"))).parse(remain1)?;
         match &quote as &str {
             // escaped
-            "「「" => result.push_str("「"),
-            "」」" => result.push_str("」"),
+            "「「" => result.push('「'),
+            "」」" => result.push('」'),
             // unescaped
             "「" => {
                 return Err(ParseError::new(remain1, ERR_OPEN_QUOTE_UNESCAPED)
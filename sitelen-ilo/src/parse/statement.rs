@@ -0,0 +1,495 @@
+use nom::{
+    Input, Parser,
+    branch::alt,
+    bytes::complete::take_while,
+    character::{anychar, complete::char},
+    combinator::{opt, value, verify},
+    sequence::pair,
+};
+use sitelen_ilo_macros::{sp, sp_c};
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        condition::Condition,
+        object::Object,
+        statement::{
+            Action, ArgKey, Argument, BlockStart, BlockType, Command, Comment, ConditionEval,
+            LoopControl, LoopControlKind, Target,
+        },
+    },
+    parse::{
+        Span,
+        condition::condition,
+        error::{ParseError, ParseResult, nom_force_failure},
+        object::object,
+        util::{CharSitelenPonaExt, span_between},
+    },
+};
+
+const ERR_MISS_O: &str = "missing ['󱥄'] before action";
+const ERR_BAD_TARGET: &str = "only variables and ['󱤎'] can be targets";
+const ERR_MISS_ACTION: &str = "expected an action after ['󱥄']";
+const ERR_ARG_OBJECT: &str = "expected an object after argument keyword";
+const ERR_ARG_ORDER: &str = "['󱤉'] arguments must come before prepositional arguments";
+const HELP_ARG_ORDER: &str =
+    "move this argument before the first one using ['󱥩'], ['󱥧'], ['󱤙'], or ['󱥖']";
+const ERR_TRAILING: &str = "expected an argument or the end of the statement";
+const ERR_ASSIGN_ARGS: &str = "an assignment takes no arguments";
+const HELP_ASSIGN_ARGS: &str = "start another action using ['󱥄']";
+
+const ERR_MISS_LA: &str = "missing ['󱤡'] after condition";
+const ERR_MISS_STATEMENT: &str = "expected a statement";
+const ERR_PINI_COND: &str = "['󱥐'] may not be preceded by a condition";
+const ERR_CHAIN_STATEMENT: &str = "only blocks can be chained using ['󱤂󱤡']";
+const HELP_CHAIN_STATEMENT: &str = "start a block here using ['󱥄󱥉']";
+
+/// A single line of a program, before blocks are assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Line {
+    /// A statement that stands on its own.
+    Statement(Statement),
+    /// The start of a block (*o pali* or *o sike*).
+    BlockStart(BlockStart),
+    /// The end of a block (*pini*).
+    BlockEnd,
+    /// A line that failed to parse. If it looks like it starts a block, that block is kept so the
+    /// lines after it still nest properly.
+    Invalid(Option<BlockStart>),
+}
+
+/// Checks if a character begins an argument.
+pub(super) fn is_arg_key(c: char) -> bool {
+    matches!(
+        c,
+        sp_c!("e") | sp_c!("tawa") | sp_c!("tan") | sp_c!("kepeken") | sp_c!("sama")
+    )
+}
+
+/// Parses the keyword that begins an argument.
+fn arg_key(input: Span) -> ParseResult<ArgKey> {
+    alt((
+        value(ArgKey::E, char(sp_c!("e"))),
+        value(ArgKey::Tawa, char(sp_c!("tawa"))),
+        value(ArgKey::Tan, char(sp_c!("tan"))),
+        value(ArgKey::Kepeken, char(sp_c!("kepeken"))),
+        value(ArgKey::Sama, char(sp_c!("sama"))),
+    ))
+    .parse_complete(input)
+}
+
+/// Parses an argument (an [`ArgKey`] followed by an object).
+fn argument(input: Span) -> ParseResult<Argument> {
+    let (input1, key) = arg_key(input)?;
+    let (input2, value) = object(input1).map_err(|err| match err {
+        nom::Err::Error(_) => ParseError::new(input, ERR_ARG_OBJECT).into_failure(),
+        _ => err,
+    })?;
+
+    let span = SrcSpan::between(input, input2);
+    Ok((input2, Argument { key, value, span }))
+}
+
+/// Parses the target of a command (either *ilo* or a variable).
+fn target(input: Span) -> ParseResult<Target> {
+    let parse_variable = |input| {
+        let (input1, obj) = object(input)?;
+        match obj {
+            Object::Variable(var) => Ok((input1, Target::Variable(var))),
+            _ => Err(ParseError::new(span_between(input, input1), ERR_BAD_TARGET).into_failure()),
+        }
+    };
+
+    alt((value(Target::Ilo, char(sp_c!("ilo"))), parse_variable)).parse_complete(input)
+}
+
+/// Parses an action. Actions are one or more words, of which only the first may be a preposition.
+fn action(input: Span) -> ParseResult<String> {
+    let (input1, c0) = verify(anychar, |&c: &char| {
+        c.is_sp_word() && !matches!(c, sp_c!("o") | sp_c!("e"))
+    })
+    .parse_complete(input)
+    .map_err(ParseError::override_reason(ERR_MISS_ACTION))
+    .map_err(nom_force_failure)?;
+    let (input2, rest) =
+        take_while(|c: char| c.is_sp_word() && c != sp_c!("o") && !is_arg_key(c))(input1)?;
+
+    let value = format!("{}{}", c0, rest);
+    Ok((input2, value))
+}
+
+/// Parses the arguments to an action, up to the next *o* or the end of the input.
+fn arguments(input: Span) -> ParseResult<Vec<Argument>> {
+    let mut args = Vec::new();
+    let mut seen_prep = false;
+
+    let mut remain = input;
+    while !remain.is_empty() && !remain.starts_with(sp_c!("o")) {
+        let (remain1, arg) = argument(remain).map_err(|err| match err {
+            nom::Err::Error(_) => ParseError::new(remain, ERR_TRAILING).into_failure(),
+            _ => err,
+        })?;
+
+        if arg.key == ArgKey::E && seen_prep {
+            return Err(
+                ParseError::new(span_between(remain, remain1), ERR_ARG_ORDER)
+                    .with_help(HELP_ARG_ORDER)
+                    .into_failure(),
+            );
+        }
+        seen_prep |= arg.key != ArgKey::E;
+
+        args.push(arg);
+        remain = remain1;
+    }
+
+    Ok((remain, args))
+}
+
+/// Parses one action (started by *o*) and its arguments.
+fn command_action<'a>(input: Span<'a>, target: &Option<Target>) -> ParseResult<'a, Action> {
+    let (input1, _) = char(sp_c!("o"))(input)?;
+
+    // variables can be assigned to directly
+    let assignment = match target {
+        Some(Target::Variable(_)) => match object(input1) {
+            Ok((input2, value)) => Some((input2, value)),
+            Err(nom::Err::Error(_)) => None,
+            Err(err) => return Err(err),
+        },
+        _ => None,
+    };
+
+    let (input3, name, args) = match assignment {
+        Some((input2, value)) => {
+            // the value is the only argument
+            if !input2.is_empty() && !input2.starts_with(sp_c!("o")) {
+                let err = match argument(input2) {
+                    Ok((input3, _)) => {
+                        ParseError::new(span_between(input2, input3), ERR_ASSIGN_ARGS)
+                            .with_help(HELP_ASSIGN_ARGS)
+                    }
+                    Err(_) => ParseError::new(input2, ERR_TRAILING),
+                };
+                return Err(err.into_failure());
+            }
+            let span = SrcSpan::between(input1, input2);
+            let arg = Argument {
+                key: ArgKey::E,
+                value,
+                span,
+            };
+            (input2, String::new(), vec![arg])
+        }
+        None => {
+            let (input2, name) = action(input1)?;
+            let (input3, args) = arguments(input2)?;
+            (input3, name, args)
+        }
+    };
+
+    let action = Action {
+        name,
+        args,
+        span: SrcSpan::between(input, input3),
+    };
+    Ok((input3, action))
+}
+
+/// Parses a command statement, without its condition.
+pub(super) fn command(input: Span) -> ParseResult<Command> {
+    let (input1, target) = opt(target).parse_complete(input)?;
+    let (mut remain, first) = command_action(input1, &target).map_err(|err| match err {
+        // without a target, this probably isn't a command
+        nom::Err::Error(_) => {
+            let err = ParseError::new(input1, ERR_MISS_O);
+            match target {
+                Some(_) => err.into_failure(),
+                None => err.into_error(),
+            }
+        }
+        _ => err,
+    })?;
+
+    // each further action is started by another o
+    let mut actions = vec![first];
+    while remain.starts_with(sp_c!("o")) {
+        let action;
+        (remain, action) = command_action(remain, &target)?;
+        actions.push(action);
+    }
+
+    let command = Command {
+        condition: None,
+        target,
+        actions,
+        span: SrcSpan::between(input, remain),
+    };
+    Ok((remain, command))
+}
+
+/// Parses a condition followed by *la*.
+fn condition_la(input: Span) -> ParseResult<Condition> {
+    let (input1, cond) = condition(input)?;
+    let (input2, _) = char(sp_c!("la"))(input1)
+        .map_err(ParseError::override_reason(ERR_MISS_LA))
+        .map_err(nom_force_failure)?;
+    Ok((input2, cond))
+}
+
+/// Parses an entire line, which is either a statement or part of a block.
+pub(super) fn line(input: Span) -> ParseResult<Line> {
+    // len
+    if let Ok((input1, _)) = char::<_, ParseError>(sp_c!("len")).parse_complete(input) {
+        let input2 = input1.take_from(input1.len());
+        let comment = Comment {
+            content: input1.trim_start().to_string(),
+            span: SrcSpan::between(input, input2),
+        };
+        return Ok((input2, Line::Statement(Statement::Comment(comment))));
+    }
+
+    // ken la
+    if let Ok((input1, _)) =
+        pair(char::<_, ParseError>(sp_c!("ken")), char(sp_c!("la"))).parse_complete(input)
+    {
+        let (input2, condition) = condition(input1).map_err(nom_force_failure)?;
+        let eval = ConditionEval {
+            condition,
+            span: SrcSpan::between(input, input2),
+        };
+        return Ok((input2, Line::Statement(Statement::ConditionEval(eval))));
+    }
+
+    // ala la, then the condition
+    let (input1, chained) =
+        opt(pair(char(sp_c!("ala")), char(sp_c!("la")))).parse_complete(input)?;
+    let chained = chained.is_some();
+    let (input2, condition) = opt(condition_la).parse_complete(input1)?;
+
+    let block_type = match *input2.fragment() {
+        sp!("o pali") => Some(BlockType::Pali),
+        sp!("o sike") => Some(BlockType::Sike),
+        _ => None,
+    };
+    if let Some(block_type) = block_type {
+        let input3 = input2.take_from(input2.len());
+        let start = BlockStart {
+            chained,
+            condition,
+            block_type,
+            span: SrcSpan::between(input, input3),
+        };
+        return Ok((input3, Line::BlockStart(start)));
+    }
+
+    if *input2.fragment() == sp!("pini") {
+        if chained || condition.is_some() {
+            return Err(ParseError::new(input, ERR_PINI_COND).into_failure());
+        }
+        return Ok((input2.take_from(input2.len()), Line::BlockEnd));
+    }
+    if chained {
+        return Err(ParseError::new(input, ERR_CHAIN_STATEMENT)
+            .with_help(HELP_CHAIN_STATEMENT)
+            .into_failure());
+    }
+
+    let loop_control = match *input2.fragment() {
+        sp!("sike o pini") => Some(LoopControlKind::Pini),
+        sp!("sike o sin") => Some(LoopControlKind::Sin),
+        _ => None,
+    };
+    if let Some(kind) = loop_control {
+        let input3 = input2.take_from(input2.len());
+        let control = LoopControl {
+            condition,
+            kind,
+            span: SrcSpan::between(input, input3),
+        };
+        return Ok((input3, Line::Statement(Statement::LoopControl(control))));
+    }
+
+    let (input3, mut command) = command(input2).map_err(|err| match err {
+        nom::Err::Error(_) => ParseError::new(input2, ERR_MISS_STATEMENT).into_failure(),
+        _ => err,
+    })?;
+    command.condition = condition;
+    command.span = SrcSpan::between(input, input3);
+    Ok((input3, Line::Statement(Statement::Command(command))))
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::{
+            object::{Literal, NiRef, Object, PrimitiveType, Variable},
+            statement::{ArgKey, Command, Target},
+        },
+        parse::{Span, statement::command},
+    };
+
+    fn check_valid(
+        test_val: &str,
+        target: Option<Target>,
+        action: &str,
+        args: &[(ArgKey, Object)],
+    ) {
+        let mut span: Span = Span::new(test_val);
+
+        let value: Command;
+        (span, value) = command(span).expect("parser should not error");
+
+        assert_eq!(value.condition, None);
+        assert_eq!(value.target, target);
+        let [value_action] = &value.actions[..] else {
+            panic!("expected a single action, got {:?}", value.actions);
+        };
+        assert_eq!(value_action.name, action);
+        let value_args: Vec<_> = value_action
+            .args
+            .iter()
+            .cloned()
+            .map(|arg| (arg.key, arg.value))
+            .collect();
+        assert_eq!(value_args, args);
+        assert_eq!(value.span.len, test_val.len());
+        assert!(span.is_empty());
+    }
+    fn check_invalid(test_val: &str, err_fragment: &str) {
+        let span: Span = Span::new(test_val);
+        let err = command(span).expect_err("parser should fail");
+        match err {
+            nom::Err::Failure(err) => assert_eq!(*err.span().fragment(), err_fragment),
+            _ => panic!("parser should fail unrecoverably"),
+        }
+    }
+
+    fn nanpa(value: i64) -> Object {
+        Object::Literal(Literal::Nanpa(value))
+    }
+    fn toki(value: &str) -> Object {
+        Object::Literal(Literal::Toki(value.into()))
+    }
+    fn variable(ptype: PrimitiveType, name: &str) -> Variable {
+        Variable {
+            ptype,
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn test_no_target() {
+        check_valid(
+            sp!("o nanpa ni e nanpa [wan]"),
+            None,
+            sp!("nanpa ni"),
+            &[(
+                ArgKey::E,
+                Object::Variable(variable(PrimitiveType::Nanpa, sp!("wan"))),
+            )],
+        );
+        check_valid(
+            sp!("o weka e nanpa <tu> e nanpa <wan> tan nanpa <luka>"),
+            None,
+            sp!("weka"),
+            &[
+                (ArgKey::E, nanpa(2)),
+                (ArgKey::E, nanpa(1)),
+                (ArgKey::Tan, nanpa(5)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_ilo_target() {
+        check_valid(
+            sp!("ilo o toki e toki <pona> e nanpa ni"),
+            Some(Target::Ilo),
+            sp!("toki"),
+            &[
+                (ArgKey::E, toki(sp!("pona"))),
+                (
+                    ArgKey::E,
+                    Object::Ni(NiRef {
+                        ptype: PrimitiveType::Nanpa,
+                    }),
+                ),
+            ],
+        );
+        check_valid(
+            sp!("ilo o pini linja"),
+            Some(Target::Ilo),
+            sp!("pini linja"),
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_preposition_action() {
+        check_valid(
+            sp!("o tawa tawa nanpa <wan>"),
+            None,
+            sp!("tawa"),
+            &[(ArgKey::Tawa, nanpa(1))],
+        );
+    }
+
+    #[test]
+    fn test_variable_target() {
+        let var = variable(PrimitiveType::Lon, sp!("pona"));
+        check_valid(
+            sp!("lon [pona] o sin"),
+            Some(Target::Variable(var.clone())),
+            sp!("sin"),
+            &[],
+        );
+        check_valid(
+            sp!("lon [pona] o lon <lon>"),
+            Some(Target::Variable(var)),
+            "",
+            &[(ArgKey::E, Object::Literal(Literal::Lon(true)))],
+        );
+    }
+
+    #[test]
+    fn test_multiple_actions() {
+        let test_val = sp!("nanpa [pona] o sin o nanpa <wan> o wan e nanpa [pona] e nanpa ni");
+        let (span, value) = command(Span::new(test_val)).expect("parser should not error");
+        assert!(span.is_empty());
+
+        let names: Vec<_> = value.actions.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, [sp!("sin"), "", sp!("wan")]);
+        let arg_counts: Vec<_> = value.actions.iter().map(|a| a.args.len()).collect();
+        assert_eq!(arg_counts, [0, 1, 2]);
+        assert_eq!(value.span.len, test_val.len());
+    }
+
+    #[test]
+    fn test_failures() {
+        check_invalid(
+            sp!("o weka tan nanpa <luka> e nanpa <wan>"),
+            sp!("e nanpa <wan>"),
+        );
+        check_invalid(
+            sp!("o pana e nanpa <wan> kepeken toki <a> e lon <ala>"),
+            sp!("e lon <ala>"),
+        );
+        check_invalid(sp!("o pana e nanpa <suli>"), sp!("suli>"));
+        check_invalid(sp!("o pana e jan"), sp!("e jan"));
+        check_invalid(sp!("o pana e"), sp!("e"));
+        check_invalid(sp!("toki <a> o pana"), sp!("toki <a>"));
+        check_invalid(sp!("nanpa [a] pana"), sp!("pana"));
+        check_invalid(sp!("ilo o"), "");
+        check_invalid(sp!("ilo o pini linja o"), "");
+        check_invalid(sp!("ilo o pini linja o e toki <a>"), sp!("e toki <a>"));
+        check_invalid(
+            sp!("nanpa [a] o nanpa <wan> e toki <a> tawa nanpa <tu>"),
+            sp!("e toki <a>"),
+        );
+        check_invalid(sp!("nanpa [a] o nanpa <wan> pona"), sp!("pona"));
+    }
+}
@@ -1,10 +1,16 @@
+use nom::Input;
+use unicode_general_category::{GeneralCategory, get_general_category};
+
+use crate::parse::Span;
 
 /// Functions for checking *sitelen pona* characters.
 /// 
 /// Refer to [the UCSUR proposal](https://www.kreativekorp.com/ucsur/charts/sitelen.html) for more info.
-pub(super) trait CharSitelenPonaExt: Copy {
+pub(crate) trait CharSitelenPonaExt: Copy {
     fn is_sp_word(self) -> bool;
+    #[allow(dead_code)]
     fn is_sp_punct(self) -> bool;
+    fn is_combining(self) -> bool;
 }
 
 impl CharSitelenPonaExt for char {
@@ -15,4 +21,20 @@ impl CharSitelenPonaExt for char {
     fn is_sp_punct(self) -> bool {
         matches!(self, '\u{F1990}' | '\u{F1991}' | '\u{F199C}' | '\u{F199D}')
     }
-}
\ No newline at end of file
+
+    /// Checks for combining marks (general category M), as well as the zero-width joiner and the
+    /// *sitelen pona* joiners and glyph extensions (which combine with the glyphs around them).
+    fn is_combining(self) -> bool {
+        matches!(
+            get_general_category(self),
+            GeneralCategory::NonspacingMark
+                | GeneralCategory::SpacingMark
+                | GeneralCategory::EnclosingMark
+        ) || matches!(self, '\u{200D}' | '\u{F1992}'..='\u{F199B}')
+    }
+}
+
+/// Gets the portion of `start` that comes before `end`.
+pub(super) fn span_between<'a>(start: Span<'a>, end: Span<'a>) -> Span<'a> {
+    start.take(end.location_offset() - start.location_offset())
+}
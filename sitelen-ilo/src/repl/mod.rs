@@ -0,0 +1,215 @@
+//! Interactive sessions, used when no program is given.
+
+use std::io;
+
+use sitelen_ilo_macros::{sp, sp_c};
+
+use crate::{
+    ast::object::{TypedValue, Variable},
+    diagnostic::{Renderer, Severity},
+    parse::{block_depth_change, parse_program},
+    runtime::{Runtime, Value, display_value, io::Console},
+};
+
+/// Name used for the input in diagnostics.
+const REPL_PATH: &str = "<repl>";
+const PROMPT: &str = "> ";
+const PROMPT_BLOCK: &str = "| ";
+
+const ERR_UNKNOWN_META: &str = "unknown command";
+const HELP_META: &str = "try :ni, :vars or :reset";
+const MSG_NI_EMPTY: &str = "ni has no value";
+const MSG_NO_VARS: &str = "no variables are declared";
+
+/// Shows a value the way it would be written in a program.
+fn describe_value(value: &Value) -> String {
+    let word = value.get_type().word();
+    format!(
+        "{}{}{}{}",
+        word,
+        sp_c!("<"),
+        display_value(value),
+        sp_c!(">")
+    )
+}
+
+/// Shows a variable the way it would be written in a program.
+fn describe_variable(var: &Variable) -> String {
+    let word = var.ptype.word();
+    format!("{}{}{}{}", word, sp_c!("["), var.name, sp_c!("]"))
+}
+
+/// Runs a meta-command, such as `:ni`.
+fn meta_command<C: Console>(runtime: &mut Runtime<C>, command: &str) -> io::Result<()> {
+    let mut out = String::new();
+    match command {
+        ":ni" => match runtime.ni() {
+            Some(value) => out = describe_value(value),
+            None => out = MSG_NI_EMPTY.to_string(),
+        },
+        ":vars" => {
+            let vars = runtime.variables();
+            if vars.is_empty() {
+                out = MSG_NO_VARS.to_string();
+            }
+            for (var, value) in vars {
+                let line = format!(
+                    "{}{}{}",
+                    describe_variable(var),
+                    sp!("o"),
+                    describe_value(value)
+                );
+                out = match out.is_empty() {
+                    true => line,
+                    false => out + "\n" + &line,
+                };
+            }
+        }
+        ":reset" => {
+            runtime.reset();
+            return Ok(());
+        }
+        _ => {
+            eprintln!(
+                "error: {} {} [help: {}]",
+                ERR_UNKNOWN_META, command, HELP_META
+            );
+            return Ok(());
+        }
+    }
+    runtime.console_mut().write(&(out + "\n"))
+}
+
+/// Parses and runs one complete entry, reporting any errors. The entry is written in sitelen
+/// Lasina.
+fn run_entry<C: Console>(runtime: &mut Runtime<C>, entry: &str, colour: bool) {
+    let renderer = Renderer::new(REPL_PATH, entry, colour);
+    let Some(transliteration) = crate::transliterate(entry, &renderer) else {
+        return;
+    };
+    let renderer = renderer.with_transliteration(&transliteration);
+    let source = transliteration.text.as_str();
+
+    let program = match parse_program(source) {
+        Ok(program) => program,
+        Err(errors) => {
+            for err in &errors {
+                eprint!("{}", renderer.render(Severity::Error, err));
+            }
+            return;
+        }
+    };
+    if let Err(err) = runtime.run(&program) {
+        eprint!("{}", renderer.render(Severity::Error, &err.locate(source)));
+    }
+}
+
+/// Runs an interactive session until the input ends. Variables and *ni* are kept between
+/// entries, and blocks are collected until they are closed before running.
+pub(crate) fn run<C: Console>(runtime: &mut Runtime<C>, colour: bool) -> io::Result<()> {
+    let mut buffer = String::new();
+    let mut depth = 0;
+    loop {
+        let prompt = match buffer.is_empty() {
+            true => PROMPT,
+            false => PROMPT_BLOCK,
+        };
+        runtime.console_mut().write(prompt)?;
+        let Some(line) = runtime.console_mut().read_line()? else {
+            break;
+        };
+
+        let line = line.trim();
+        if buffer.is_empty() && line.starts_with(':') {
+            meta_command(runtime, line)?;
+            continue;
+        }
+        let renderer = Renderer::new(REPL_PATH, line, colour);
+        let Some(transliteration) = crate::transliterate(line, &renderer) else {
+            continue;
+        };
+        if transliteration.text.is_empty() {
+            continue;
+        }
+
+        depth += block_depth_change(&transliteration.text);
+        buffer.push_str(line);
+        buffer.push('\n');
+        if depth <= 0 {
+            run_entry(runtime, &buffer, colour);
+            buffer.clear();
+            depth = 0;
+        }
+    }
+    // the line with the prompt was never finished
+    runtime.console_mut().write("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        repl::run,
+        runtime::{Runtime, io::Streams},
+    };
+
+    fn session(input: &'static str) -> String {
+        let mut runtime = Runtime::with_console(Streams {
+            input: input.as_bytes(),
+            output: Vec::new(),
+        });
+        run(&mut runtime, false).expect("session should not fail");
+        let output = std::mem::take(&mut runtime.console_mut().output);
+        String::from_utf8(output).expect("output should be UTF-8")
+    }
+
+    #[test]
+    fn test_session() {
+        let output = session(concat!(
+            ":ni\n",
+            "nanpa [ijo] o sin\n",
+            "o sike\n",
+            "o wan e nanpa [ijo] e nanpa <wan>\n",
+            "nanpa [ijo] o nanpa ni\n",
+            "nanpa [ijo] li suli tawa nanpa <luka> la sike o pini\n",
+            "pini\n",
+            ":vars\n",
+            ":ni\n",
+            ":reset\n",
+            ":vars\n",
+        ));
+        let expected = [
+            "> ni has no value\n",
+            "> > | | | | > ",
+            sp!("nanpa [ijo] o nanpa <luka wan>"),
+            "\n> ",
+            sp!("nanpa <luka wan>"),
+            "\n> > no variables are declared\n> \n",
+        ];
+        assert_eq!(output, expected.concat());
+    }
+
+    #[test]
+    fn test_errors() {
+        // errors are reported without ending the session
+        let output = session(concat!(
+            "ilo o toki e hello\n",
+            "ilo o toki e nanpa [ijo]\n",
+            "ilo o toki e toki <pona>\n",
+            "pini\n",
+            ":vars\n",
+        ));
+        assert_eq!(output, "> > > pona> > no variables are declared\n> \n");
+    }
+
+    #[test]
+    fn test_lasina() {
+        // comments and literals are kept as they are written, like in files
+        let output = session(concat!(
+            "len hi there!\n",
+            "ilo o toki e toki <Hello world>\n",
+        ));
+        assert_eq!(output, "> > Hello world> \n");
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt::Display;
+
+use crate::{ast::SrcSpan, parse::error::ParseError};
+
+/// Error raised while running a program.
+#[derive(Debug, Clone)]
+pub(crate) struct RuntimeError {
+    span: SrcSpan,
+    reason: String,
+    help: Option<String>,
+}
+impl RuntimeError {
+    /// Create a new [`RuntimeError`] with a location and reason.
+    pub(crate) fn new(span: SrcSpan, reason: impl Into<String>) -> Self {
+        Self {
+            span,
+            reason: reason.into(),
+            help: None,
+        }
+    }
+
+    /// Adds a help message to this error.
+    pub(crate) fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Gets the region of the source code where the error happened.
+    pub(crate) fn span(&self) -> SrcSpan {
+        self.span
+    }
+
+    /// Converts this error into a [`ParseError`] pointing into `source`, so it can be rendered.
+    pub(crate) fn locate<'a>(&self, source: &'a str) -> ParseError<'a> {
+        let err = ParseError::new(self.span.locate(source), self.reason.clone());
+        match &self.help {
+            Some(help) => err.with_help(help.clone()),
+            None => err,
+        }
+    }
+}
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.reason)?;
+        if let Some(help) = &self.help {
+            f.write_fmt(format_args!(" [help: {}]", help))?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for RuntimeError {}
+
+pub(crate) type RuntimeResult<T> = Result<T, RuntimeError>;
@@ -0,0 +1,613 @@
+//! Intrinsic operations, run by commands without a target.
+
+use sitelen_ilo_macros::sp;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        object::{Literal, Object, PrimitiveType, TypedValue},
+        statement::{ArgKey, Argument},
+    },
+    parse::object::{lon_from_text, nanpa_from_text},
+    runtime::{
+        Value, display_value,
+        error::{RuntimeError, RuntimeResult},
+    },
+};
+
+const ERR_MISS_ARG: &str = "missing argument for this action";
+const ERR_BAD_ARG: &str = "unexpected argument for this action";
+pub(crate) const ERR_OVERFLOW: &str = "value out of range for nanpa";
+pub(crate) const ERR_DIV_ZERO: &str = "cannot divide by zero";
+pub(crate) const ERR_INDEX_RANGE: &str = "index out of range for toki";
+pub(crate) const ERR_SUBSTR_ORDER: &str = "substring cannot end before it starts";
+pub(crate) const ERR_NOT_CHAR: &str = "toki must contain exactly one character";
+pub(crate) const ERR_CODEPOINT: &str = "not a valid Unicode codepoint";
+
+/// The key and type of an argument. A type of `None` accepts any type.
+type ArgSpec = (ArgKey, Option<PrimitiveType>);
+
+/// Describes how the arguments of an action are laid out.
+pub(super) struct Layout {
+    /// Arguments that come first, in order.
+    head: &'static [ArgSpec],
+    /// An argument that is repeated one or more times after the head.
+    repeat: Option<ArgSpec>,
+    /// Arguments that come last, in order.
+    tail: &'static [ArgSpec],
+}
+impl Layout {
+    /// Layout with a fixed list of arguments.
+    pub(super) const fn fixed(args: &'static [ArgSpec]) -> Self {
+        Self {
+            head: args,
+            repeat: None,
+            tail: &[],
+        }
+    }
+
+    /// Layout with one argument, repeated one or more times.
+    pub(super) const fn repeated(arg: ArgSpec) -> Self {
+        Self {
+            head: &[],
+            repeat: Some(arg),
+            tail: &[],
+        }
+    }
+
+    /// Describes the layout, for help messages.
+    fn describe(&self) -> String {
+        let spec = |&(key, ptype): &ArgSpec| match ptype {
+            Some(ptype) => format!("{}{}", key.word(), ptype),
+            None => format!("{}[…]", key.word()),
+        };
+
+        let mut parts: Vec<String> = self.head.iter().map(spec).collect();
+        if let Some(repeat) = &self.repeat {
+            parts.push(format!("{}…", spec(repeat)));
+        }
+        parts.extend(self.tail.iter().map(spec));
+        format!("this action takes {}", parts.join(" "))
+    }
+
+    /// Checks that some arguments match this layout. `span` is used if arguments are missing.
+    pub(super) fn check(&self, args: &[Argument], span: SrcSpan) -> RuntimeResult<()> {
+        let matches = |arg: &Argument, &(key, ptype): &ArgSpec| {
+            arg.key == key && ptype.is_none_or(|t| t == arg.value.get_type())
+        };
+        let error = |arg: Option<&Argument>| {
+            let err = match arg {
+                Some(arg) => RuntimeError::new(arg.span, ERR_BAD_ARG),
+                None => RuntimeError::new(span, ERR_MISS_ARG),
+            };
+            Err(err.with_help(self.describe()))
+        };
+
+        let mut args = args.iter().peekable();
+        for spec in self.head {
+            match args.next() {
+                Some(arg) if matches(arg, spec) => (),
+                arg => return error(arg),
+            }
+        }
+        if let Some(spec) = &self.repeat {
+            match args.next() {
+                Some(arg) if matches(arg, spec) => (),
+                arg => return error(arg),
+            }
+            while args.next_if(|arg| matches(arg, spec)).is_some() {}
+        }
+        for spec in self.tail {
+            match args.next() {
+                Some(arg) if matches(arg, spec) => (),
+                arg => return error(arg),
+            }
+        }
+        match args.next() {
+            Some(arg) => error(Some(arg)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An evaluated argument.
+pub(super) struct Operand {
+    pub(super) value: Value,
+    pub(super) span: SrcSpan,
+}
+impl Operand {
+    /// Gets the value of a *nanpa* operand. The layout must have already been checked.
+    fn nanpa(&self) -> i64 {
+        match self.value {
+            Literal::Nanpa(value) => value,
+            _ => unreachable!("layout should ensure operand is nanpa"),
+        }
+    }
+
+    /// Gets the value of a *toki* operand. The layout must have already been checked.
+    fn toki(&self) -> &str {
+        match &self.value {
+            Literal::Toki(value) => value,
+            _ => unreachable!("layout should ensure operand is toki"),
+        }
+    }
+}
+
+/// An intrinsic operation. The result is saved to *ni*.
+pub(super) struct Intrinsic {
+    pub(super) action: &'static str,
+    pub(super) layout: Layout,
+    /// The type of the result, or `None` if it has the type of the first argument.
+    pub(super) result: Option<PrimitiveType>,
+    pub(super) run: fn(&[Operand]) -> RuntimeResult<Value>,
+}
+
+const NANPA: Option<PrimitiveType> = Some(PrimitiveType::Nanpa);
+const TOKI: Option<PrimitiveType> = Some(PrimitiveType::Toki);
+const LON: Option<PrimitiveType> = Some(PrimitiveType::Lon);
+
+static INTRINSICS: &[Intrinsic] = &[
+    Intrinsic {
+        action: sp!("sona"),
+        layout: Layout::fixed(&[(ArgKey::E, None)]),
+        result: None,
+        run: set_ni,
+    },
+    Intrinsic {
+        action: sp!("wan"),
+        layout: Layout::repeated((ArgKey::E, NANPA)),
+        result: NANPA,
+        run: add,
+    },
+    Intrinsic {
+        action: sp!("weka"),
+        layout: Layout {
+            head: &[],
+            repeat: Some((ArgKey::E, NANPA)),
+            tail: &[(ArgKey::Tan, NANPA)],
+        },
+        result: NANPA,
+        run: subtract,
+    },
+    Intrinsic {
+        action: sp!("mute"),
+        layout: Layout::repeated((ArgKey::E, NANPA)),
+        result: NANPA,
+        run: multiply,
+    },
+    Intrinsic {
+        action: sp!("kipisi"),
+        layout: Layout {
+            head: &[(ArgKey::E, NANPA)],
+            repeat: Some((ArgKey::Tawa, NANPA)),
+            tail: &[],
+        },
+        result: NANPA,
+        run: divide,
+    },
+    Intrinsic {
+        action: sp!("pana kipisi"),
+        layout: Layout::fixed(&[(ArgKey::E, NANPA), (ArgKey::Kepeken, NANPA)]),
+        result: NANPA,
+        run: remainder,
+    },
+    Intrinsic {
+        action: sp!("wan linja"),
+        layout: Layout::repeated((ArgKey::E, TOKI)),
+        result: TOKI,
+        run: concat,
+    },
+    Intrinsic {
+        action: sp!("jo nimi"),
+        layout: Layout::fixed(&[(ArgKey::E, NANPA), (ArgKey::Tan, TOKI)]),
+        result: TOKI,
+        run: char_at,
+    },
+    Intrinsic {
+        action: sp!("jo linja"),
+        layout: Layout::fixed(&[
+            (ArgKey::E, NANPA),
+            (ArgKey::Tawa, NANPA),
+            (ArgKey::Tan, TOKI),
+        ]),
+        result: TOKI,
+        run: substring,
+    },
+    Intrinsic {
+        action: sp!("nanpa nimi"),
+        layout: Layout::fixed(&[(ArgKey::E, TOKI)]),
+        result: NANPA,
+        run: char_to_code,
+    },
+    Intrinsic {
+        action: sp!("nimi nanpa"),
+        layout: Layout::fixed(&[(ArgKey::E, NANPA)]),
+        result: TOKI,
+        run: code_to_char,
+    },
+    Intrinsic {
+        action: sp!("ante nanpa"),
+        layout: Layout::fixed(&[(ArgKey::E, None)]),
+        result: NANPA,
+        run: to_nanpa,
+    },
+    Intrinsic {
+        action: sp!("ante lon"),
+        layout: Layout::fixed(&[(ArgKey::E, None)]),
+        result: LON,
+        run: to_lon,
+    },
+    Intrinsic {
+        action: sp!("ante toki"),
+        layout: Layout::fixed(&[(ArgKey::E, None)]),
+        result: TOKI,
+        run: to_toki,
+    },
+];
+
+/// Finds the intrinsic for an action.
+pub(super) fn find(action: &str) -> Option<&'static Intrinsic> {
+    INTRINSICS.iter().find(|op| op.action == action)
+}
+
+/// Finds the index of the intrinsic for an action, for use in bytecode.
+pub(super) fn position(action: &str) -> Option<usize> {
+    INTRINSICS.iter().position(|op| op.action == action)
+}
+
+/// Gets the action of the intrinsic with an index.
+pub(crate) fn action(index: usize) -> &'static str {
+    INTRINSICS[index].action
+}
+
+/// Gets an intrinsic by its index.
+pub(super) fn by_index(index: usize) -> &'static Intrinsic {
+    &INTRINSICS[index]
+}
+
+/// Gets the type an intrinsic saves to *ni*, or `None` if there is no such intrinsic.
+pub(crate) fn result_type(action: &str, args: &[Argument]) -> Option<PrimitiveType> {
+    let op = find(action)?;
+    op.result
+        .or_else(|| args.first().map(|arg| arg.value.get_type()))
+}
+
+/// Runs an intrinsic ahead of time, if all of its arguments are literals. Returns `None` if it
+/// can't be run, or if running it would fail, so that the error is still raised at runtime.
+pub(crate) fn fold(action: &str, args: &[Argument], span: SrcSpan) -> Option<Value> {
+    let op = find(action)?;
+    op.layout.check(args, span).ok()?;
+    let operands = args
+        .iter()
+        .map(|arg| match &arg.value {
+            Object::Literal(lit) => Some(Operand {
+                value: lit.clone(),
+                span: arg.span,
+            }),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (op.run)(&operands).ok()
+}
+
+/// `󱥄󱥡󱤉[value]`
+fn set_ni(ops: &[Operand]) -> RuntimeResult<Value> {
+    Ok(ops[0].value.clone())
+}
+
+/// `󱥄󱥳󱤉[n1]󱤉[n2]󱤉[n3]`
+fn add(ops: &[Operand]) -> RuntimeResult<Value> {
+    let mut result = 0i64;
+    for op in ops {
+        result = result
+            .checked_add(op.nanpa())
+            .ok_or_else(|| RuntimeError::new(op.span, ERR_OVERFLOW))?;
+    }
+    Ok(Literal::Nanpa(result))
+}
+
+/// `󱥄󱥶󱤉[n1]󱤉[n2]󱥧[k]`
+fn subtract(ops: &[Operand]) -> RuntimeResult<Value> {
+    let (k, ns) = ops
+        .split_last()
+        .expect("layout should ensure operands exist");
+    let mut result = k.nanpa();
+    for op in ns {
+        result = result
+            .checked_sub(op.nanpa())
+            .ok_or_else(|| RuntimeError::new(op.span, ERR_OVERFLOW))?;
+    }
+    Ok(Literal::Nanpa(result))
+}
+
+/// `󱥄󱤼󱤉[n1]󱤉[n2]󱤉[n3]`
+fn multiply(ops: &[Operand]) -> RuntimeResult<Value> {
+    let mut result = 1i64;
+    for op in ops {
+        result = result
+            .checked_mul(op.nanpa())
+            .ok_or_else(|| RuntimeError::new(op.span, ERR_OVERFLOW))?;
+    }
+    Ok(Literal::Nanpa(result))
+}
+
+/// `󱥄󱥻󱤉[k]󱥩[n1]󱥩[n2]`
+fn divide(ops: &[Operand]) -> RuntimeResult<Value> {
+    let (k, ns) = ops
+        .split_first()
+        .expect("layout should ensure operands exist");
+    let mut result = k.nanpa();
+    for op in ns {
+        let n = op.nanpa();
+        if n == 0 {
+            return Err(RuntimeError::new(op.span, ERR_DIV_ZERO));
+        }
+        result = result
+            .checked_div(n)
+            .ok_or_else(|| RuntimeError::new(op.span, ERR_OVERFLOW))?;
+    }
+    Ok(Literal::Nanpa(result))
+}
+
+/// `󱥄󱥌󱥻󱤉[k]󱤙[n1]`
+fn remainder(ops: &[Operand]) -> RuntimeResult<Value> {
+    let (k, n) = (ops[0].nanpa(), ops[1].nanpa());
+    if n == 0 {
+        return Err(RuntimeError::new(ops[1].span, ERR_DIV_ZERO));
+    }
+    Ok(Literal::Nanpa(k.wrapping_rem(n)))
+}
+
+/// Finds the byte offset of the `index`-th character in `s`. The length of `s` is a valid offset.
+fn char_offset(s: &str, index: &Operand) -> RuntimeResult<usize> {
+    usize::try_from(index.nanpa())
+        .ok()
+        .and_then(|i| s.char_indices().map(|(b, _)| b).chain([s.len()]).nth(i))
+        .ok_or_else(|| RuntimeError::new(index.span, ERR_INDEX_RANGE))
+}
+
+/// `󱥄󱥳󱤩󱤉[s1]󱤉[s2]󱤉[s3]`
+fn concat(ops: &[Operand]) -> RuntimeResult<Value> {
+    Ok(Literal::Toki(ops.iter().map(Operand::toki).collect()))
+}
+
+/// `󱥄󱤓󱥂󱤉[i]󱥧[s]`
+fn char_at(ops: &[Operand]) -> RuntimeResult<Value> {
+    let s = ops[1].toki();
+    let start = char_offset(s, &ops[0])?;
+    let c = s[start..]
+        .chars()
+        .next()
+        .ok_or_else(|| RuntimeError::new(ops[0].span, ERR_INDEX_RANGE))?;
+    Ok(Literal::Toki(c.to_string()))
+}
+
+/// `󱥄󱤓󱤩󱤉[i]󱥩[j]󱥧[s]`
+fn substring(ops: &[Operand]) -> RuntimeResult<Value> {
+    let s = ops[2].toki();
+    let start = char_offset(s, &ops[0])?;
+    let end = char_offset(s, &ops[1])?;
+    if end < start {
+        return Err(RuntimeError::new(ops[1].span, ERR_SUBSTR_ORDER));
+    }
+    Ok(Literal::Toki(s[start..end].to_string()))
+}
+
+/// `󱥄󱤽󱥂󱤉[c]`
+fn char_to_code(ops: &[Operand]) -> RuntimeResult<Value> {
+    let mut chars = ops[0].toki().chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Literal::Nanpa(c as i64)),
+        _ => Err(RuntimeError::new(ops[0].span, ERR_NOT_CHAR)),
+    }
+}
+
+/// `󱥄󱥂󱤽󱤉[i]`
+fn code_to_char(ops: &[Operand]) -> RuntimeResult<Value> {
+    u32::try_from(ops[0].nanpa())
+        .ok()
+        .and_then(char::from_u32)
+        .map(|c| Literal::Toki(c.to_string()))
+        .ok_or_else(|| RuntimeError::new(ops[0].span, ERR_CODEPOINT))
+}
+
+/// `󱥄󱤆󱤽󱤉[s]`
+fn to_nanpa(ops: &[Operand]) -> RuntimeResult<Value> {
+    let value = match &ops[0].value {
+        Literal::Toki(text) => {
+            nanpa_from_text(text).map_err(|reason| RuntimeError::new(ops[0].span, reason))?
+        }
+        Literal::Nanpa(value) => *value,
+        Literal::Lon(value) => *value as i64,
+    };
+    Ok(Literal::Nanpa(value))
+}
+
+/// `󱥄󱤆󱤬󱤉[s]`
+fn to_lon(ops: &[Operand]) -> RuntimeResult<Value> {
+    let value = match &ops[0].value {
+        Literal::Toki(text) => {
+            lon_from_text(text).map_err(|reason| RuntimeError::new(ops[0].span, reason))?
+        }
+        Literal::Nanpa(value) => *value != 0,
+        Literal::Lon(value) => *value,
+    };
+    Ok(Literal::Lon(value))
+}
+
+/// `󱥄󱤆󱥬󱤉[s]`
+fn to_toki(ops: &[Operand]) -> RuntimeResult<Value> {
+    Ok(Literal::Toki(display_value(&ops[0].value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::{sp, sp_c};
+
+    use crate::{
+        ast::object::Literal,
+        parse::parse_program,
+        runtime::{Runtime, Value, error::RuntimeError, io::Streams},
+    };
+
+    fn test_runtime() -> Runtime<Streams<&'static [u8], Vec<u8>>> {
+        Runtime::with_console(Streams {
+            input: "".as_bytes(),
+            output: Vec::new(),
+        })
+    }
+
+    fn check_valid(program: &str, ni: Value) {
+        let stmts = parse_program(program).expect("program should parse");
+        let mut runtime = test_runtime();
+        runtime.run(&stmts).expect("program should run");
+        assert_eq!(runtime.ni, Some(ni));
+    }
+    fn check_invalid(program: &str, err_arg: &str) -> RuntimeError {
+        let stmts = parse_program(program).expect("program should parse");
+        let mut runtime = test_runtime();
+        let err = runtime.run(&stmts).expect_err("program should fail");
+        assert_eq!(*err.locate(program).span().fragment(), err_arg);
+        err
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        check_valid(
+            sp!("o wan e nanpa <tu> e nanpa <tu wan> e nanpa <luka>"),
+            Literal::Nanpa(10),
+        );
+        check_valid(
+            sp!("o weka e nanpa <tu> e nanpa <wan> tan nanpa <luka>"),
+            Literal::Nanpa(2),
+        );
+        check_valid(
+            sp!("o mute e nanpa <tu> e nanpa <tu wan> e nanpa <luka>"),
+            Literal::Nanpa(30),
+        );
+        check_valid(
+            sp!("o kipisi e nanpa <mute tu tu> tawa nanpa <tu> tawa nanpa <tu wan>"),
+            Literal::Nanpa(4),
+        );
+        check_valid(
+            sp!("o pana kipisi e nanpa <mute tu> kepeken nanpa <luka>"),
+            Literal::Nanpa(2),
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        check_valid(
+            sp!("o wan linja e toki <jan> e toki <> e toki <pona>"),
+            Literal::Toki(sp!("jan pona").into()),
+        );
+        check_valid(
+            sp!("o jo nimi e nanpa <tu> tan toki <toki pona li pona>"),
+            Literal::Toki(sp!("li").into()),
+        );
+        check_valid(
+            sp!("o jo linja e nanpa <wan> tawa nanpa <tu wan> tan toki <toki pona li pona>"),
+            Literal::Toki(sp!("pona li").into()),
+        );
+        check_valid(
+            sp!("o jo linja e nanpa <tu> tawa nanpa <tu> tan toki <toki pona li pona>"),
+            Literal::Toki(String::new()),
+        );
+        check_valid(
+            sp!("o nanpa nimi e toki <pona>"),
+            Literal::Nanpa(sp_c!("pona") as i64),
+        );
+        check_valid(
+            sp!("o nimi nanpa e nanpa <ale ale mute mute luka luka luka tu>"),
+            Literal::Toki("ā".into()),
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        check_valid(
+            sp!("o ante nanpa e toki <mute luka tu>"),
+            Literal::Nanpa(27),
+        );
+        check_valid(sp!("o ante nanpa e lon <lon>"), Literal::Nanpa(1));
+        check_valid(sp!("o ante lon e toki <ala>"), Literal::Lon(false));
+        check_valid(sp!("o ante lon e nanpa <tu weka>"), Literal::Lon(true));
+        check_valid(sp!("o ante lon e nanpa <ala>"), Literal::Lon(false));
+        check_valid(
+            sp!("o ante toki e lon <lon>"),
+            Literal::Toki(sp!("lon").into()),
+        );
+        check_valid(
+            sp!("o ante toki e nanpa <ale mute tu weka>"),
+            Literal::Toki(sp!("wan ale mute tu weka").into()),
+        );
+        check_valid(
+            sp!("o ante toki e toki <pona>"),
+            Literal::Toki(sp!("pona").into()),
+        );
+    }
+
+    #[test]
+    fn test_failures() {
+        check_invalid(
+            sp!("o mute e nanpa <wan ale ale ale ale ale ale ale ale ale> e nanpa <luka luka>"),
+            sp!("e nanpa <luka luka>"),
+        );
+        check_invalid(sp!("o weka e nanpa <wan>"), sp!("o weka e nanpa <wan>"));
+        check_invalid(
+            sp!("o kipisi e nanpa <luka> tawa nanpa <tu> tawa nanpa <ala>"),
+            sp!("tawa nanpa <ala>"),
+        );
+        check_invalid(
+            sp!("o pana kipisi e nanpa <luka> kepeken nanpa <ala>"),
+            sp!("kepeken nanpa <ala>"),
+        );
+        check_invalid(
+            sp!("o weka e nanpa <tu> tawa nanpa <luka>"),
+            sp!("tawa nanpa <luka>"),
+        );
+        check_invalid(sp!("o mute e toki <wan>"), sp!("e toki <wan>"));
+
+        check_invalid(
+            sp!("o jo nimi e nanpa <tu> tan toki <toki pona>"),
+            sp!("e nanpa <tu>"),
+        );
+        check_invalid(
+            sp!("o jo nimi e nanpa <wan weka> tan toki <toki pona>"),
+            sp!("e nanpa <wan weka>"),
+        );
+        check_invalid(
+            sp!("o jo linja e nanpa <ala> tawa nanpa <tu wan> tan toki <toki pona>"),
+            sp!("tawa nanpa <tu wan>"),
+        );
+        check_invalid(
+            sp!("o jo linja e nanpa <tu> tawa nanpa <wan> tan toki <toki pona>"),
+            sp!("tawa nanpa <wan>"),
+        );
+        check_invalid(
+            sp!("o nanpa nimi e toki <toki pona>"),
+            sp!("e toki <toki pona>"),
+        );
+        check_invalid(sp!("o nanpa nimi e toki <>"), sp!("e toki <>"));
+        check_invalid(
+            sp!(
+                "o nimi nanpa e nanpa <luka ale mute mute luka luka tu ale mute mute mute mute luka luka luka wan>"
+            ),
+            sp!(
+                "e nanpa <luka ale mute mute luka luka tu ale mute mute mute mute luka luka luka wan>"
+            ),
+        );
+
+        let err = check_invalid(
+            sp!("o ante nanpa e toki <wan suli>"),
+            sp!("e toki <wan suli>"),
+        );
+        assert_eq!(err.to_string(), "failed to match nasin nanpa pona number");
+        let err = check_invalid(
+            sp!("o ante nanpa e toki <wan ale ale ale ale ale ale ale ale ale ale>"),
+            sp!("e toki <wan ale ale ale ale ale ale ale ale ale ale>"),
+        );
+        assert_eq!(err.to_string(), "value out of range for nanpa");
+        check_invalid(sp!("o ante lon e toki <lon ala>"), sp!("e toki <lon ala>"));
+    }
+}
@@ -0,0 +1,69 @@
+//! Console I/O used by *ilo*.
+
+use std::io::{self, BufRead, Write};
+
+/// The outside world, as seen by *ilo*.
+pub(crate) trait Console {
+    /// Writes text to the output.
+    fn write(&mut self, text: &str) -> io::Result<()>;
+
+    /// Reads a line from the input, without its line break. Returns `None` at the end of input.
+    fn read_line(&mut self) -> io::Result<Option<String>>;
+}
+
+/// The [`Console`] connected to stdin and stdout.
+pub(crate) type Stdio = Streams<io::StdinLock<'static>, io::StdoutLock<'static>>;
+
+/// A [`Console`] made from a reader and a writer.
+pub(crate) struct Streams<R, W> {
+    pub(crate) input: R,
+    pub(crate) output: W,
+}
+impl Stdio {
+    /// Connects to the terminal (or whatever stdin and stdout are).
+    pub(crate) fn stdio() -> Self {
+        Self {
+            input: io::stdin().lock(),
+            output: io::stdout().lock(),
+        }
+    }
+}
+impl<R: BufRead, W: Write> Console for Streams<R, W> {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.output.write_all(text.as_bytes())
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        // make sure prompts are visible before waiting on input
+        self.output.flush()?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::io::{Console, Streams};
+
+    #[test]
+    fn test_read_line() {
+        let mut console = Streams {
+            input: "jan\r\npona\nli".as_bytes(),
+            output: Vec::new(),
+        };
+        assert_eq!(console.read_line().unwrap().as_deref(), Some("jan"));
+        assert_eq!(console.read_line().unwrap().as_deref(), Some("pona"));
+        assert_eq!(console.read_line().unwrap().as_deref(), Some("li"));
+        assert_eq!(console.read_line().unwrap(), None);
+    }
+}
@@ -0,0 +1,521 @@
+//! Tree-walking interpreter for *sitelen ilo* programs.
+
+use std::collections::HashMap;
+
+use sitelen_ilo_macros::{sp, sp_c};
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        condition::Condition,
+        object::{Literal, Object, PrimitiveType, TypedValue, Variable},
+        statement::{Action, ArgKey, Command, LoopControlKind, Target},
+    },
+    parse::object::nanpa_to_text,
+    runtime::{
+        error::{RuntimeError, RuntimeResult},
+        intrinsic::{Layout, Operand},
+        io::{Console, Stdio},
+    },
+};
+
+pub(crate) mod error;
+pub(crate) mod intrinsic;
+pub(crate) mod io;
+pub(crate) mod vm;
+
+pub(crate) const ERR_UNDECLARED: &str = "variable is used before it is declared";
+pub(crate) const HELP_UNDECLARED: &str = "declare it first using ['󱥄󱥝']";
+pub(crate) const ERR_REDECLARED: &str = "variable is already declared in this block";
+pub(crate) const ERR_NI_EMPTY: &str = "ni does not hold a value yet";
+const ERR_UNKNOWN_ACTION: &str = "unknown action";
+pub(crate) const ERR_ASSIGN_TYPE: &str = "value does not match the type of the variable";
+pub(crate) const ERR_COMPARE_TYPES: &str = "only values of the same type can be compared";
+pub(crate) const ERR_COMPARE_NANPA: &str = "only nanpa values can be compared with ['󱥣󱥩'] or ['󱤨󱥩']";
+pub(crate) const ERR_WRITE: &str = "failed to write output";
+pub(crate) const ERR_READ: &str = "failed to read input";
+pub(crate) const ERR_CHAIN_LON: &str = "only lon values can be used with ['󱤊'] or ['󱤇']";
+
+/// A value held by a variable or *ni*.
+pub(crate) type Value = Literal;
+
+/// Gets the value a variable has when it is declared.
+fn default_value(ptype: PrimitiveType) -> Value {
+    match ptype {
+        PrimitiveType::Toki => Literal::Toki(String::new()),
+        PrimitiveType::Nanpa => Literal::Nanpa(0),
+        PrimitiveType::Lon => Literal::Lon(false),
+    }
+}
+
+/// Converts a value to text, as it would be printed.
+pub(crate) fn display_value(value: &Value) -> String {
+    match value {
+        Literal::Toki(value) => value.clone(),
+        Literal::Nanpa(value) => nanpa_to_text(*value),
+        Literal::Lon(true) => sp_c!("lon").to_string(),
+        Literal::Lon(false) => sp_c!("ala").to_string(),
+    }
+}
+
+/// State of a running program.
+pub(crate) struct Runtime<C = Stdio> {
+    /// Variables declared in each block, from outermost to innermost.
+    scopes: Vec<HashMap<Variable, Value>>,
+    /// The last result of an operation.
+    ni: Option<Value>,
+    /// Where *ilo* reads and writes text.
+    console: C,
+}
+impl Runtime {
+    /// Creates a new runtime connected to stdin and stdout, with no variables declared.
+    pub(crate) fn new() -> Self {
+        Self::with_console(Stdio::stdio())
+    }
+}
+impl<C: Console> Runtime<C> {
+    /// Creates a new runtime using a console, with no variables declared.
+    pub(crate) fn with_console(console: C) -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            ni: None,
+            console,
+        }
+    }
+
+    /// Runs a program at the top level.
+    pub(crate) fn run(&mut self, program: &[Statement]) -> RuntimeResult<()> {
+        self.exec_all(program)?;
+        Ok(())
+    }
+
+    /// Gets the value of *ni*, if it has one.
+    pub(crate) fn ni(&self) -> Option<&Value> {
+        self.ni.as_ref()
+    }
+
+    /// Lists the variables that are currently declared, sorted by name.
+    pub(crate) fn variables(&self) -> Vec<(&Variable, &Value)> {
+        let mut vars: Vec<_> = self.scopes.iter().flatten().collect();
+        vars.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        vars
+    }
+
+    /// Forgets all variables and the value of *ni*.
+    pub(crate) fn reset(&mut self) {
+        self.scopes = vec![HashMap::new()];
+        self.ni = None;
+    }
+
+    /// Gets the console this runtime reads and writes with.
+    pub(crate) fn console_mut(&mut self) -> &mut C {
+        &mut self.console
+    }
+
+    /// Runs statements in a new scope.
+    fn exec_block(&mut self, stmts: &[Statement]) -> RuntimeResult<Option<LoopControlKind>> {
+        self.scopes.push(HashMap::new());
+        let result = self.exec_all(stmts);
+        self.scopes.pop();
+        result
+    }
+
+    /// Runs statements in order, stopping early if a loop control statement is hit.
+    fn exec_all(&mut self, stmts: &[Statement]) -> RuntimeResult<Option<LoopControlKind>> {
+        for stmt in stmts {
+            if let Some(kind) = self.exec(stmt)? {
+                return Ok(Some(kind));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs a statement, returning the loop control statement hit (if any).
+    fn exec(&mut self, stmt: &Statement) -> RuntimeResult<Option<LoopControlKind>> {
+        match stmt {
+            Statement::Comment(_) => (),
+            Statement::Command(cmd) => {
+                if self.check_condition(&cmd.condition, cmd.span)? {
+                    self.exec_command(cmd)?;
+                }
+            }
+            Statement::ConditionEval(eval) => {
+                let value = self.eval_condition(&eval.condition, eval.span)?;
+                self.ni = Some(Literal::Lon(value));
+            }
+            Statement::IfLadder(ladder) => {
+                for block in &ladder.blocks {
+                    if self.check_condition(&block.start.condition, block.start.span)? {
+                        return self.exec_block(&block.contents);
+                    }
+                }
+            }
+            Statement::LoopBlock(block) => {
+                while self.check_condition(&block.start.condition, block.start.span)? {
+                    if let Some(LoopControlKind::Pini) = self.exec_block(&block.contents)? {
+                        break;
+                    }
+                }
+            }
+            Statement::LoopControl(control) => {
+                if self.check_condition(&control.condition, control.span)? {
+                    return Ok(Some(control.kind));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs the actions of a command in order, ignoring its condition.
+    fn exec_command(&mut self, cmd: &Command) -> RuntimeResult<()> {
+        for action in &cmd.actions {
+            self.exec_action(&cmd.target, action)?;
+        }
+        Ok(())
+    }
+
+    /// Runs one action on a target.
+    fn exec_action(&mut self, target: &Option<Target>, action: &Action) -> RuntimeResult<()> {
+        match target {
+            None => {
+                let op = intrinsic::find(&action.name)
+                    .ok_or_else(|| RuntimeError::new(action.span, ERR_UNKNOWN_ACTION))?;
+                op.layout.check(&action.args, action.span)?;
+                let operands = action
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        Ok(Operand {
+                            value: self.value_of(&arg.value, arg.span)?,
+                            span: arg.span,
+                        })
+                    })
+                    .collect::<RuntimeResult<Vec<_>>>()?;
+                self.ni = Some((op.run)(&operands)?);
+            }
+            Some(Target::Ilo) => match action.name.as_str() {
+                sp!("toki") => {
+                    Layout::repeated((ArgKey::E, None)).check(&action.args, action.span)?;
+                    let mut text = String::new();
+                    for arg in &action.args {
+                        text += &display_value(&self.value_of(&arg.value, arg.span)?);
+                    }
+                    self.write(&text, action.span)?;
+                }
+                sp!("pini linja") => {
+                    Layout::fixed(&[]).check(&action.args, action.span)?;
+                    self.write("\n", action.span)?;
+                }
+                sp!("wile linja") => {
+                    Layout::fixed(&[]).check(&action.args, action.span)?;
+                    let line = self.console.read_line().map_err(|err| {
+                        RuntimeError::new(action.span, format!("{}: {}", ERR_READ, err))
+                    })?;
+                    // the end of input reads as an empty line
+                    self.ni = Some(Literal::Toki(line.unwrap_or_default()));
+                }
+                _ => return Err(RuntimeError::new(action.span, ERR_UNKNOWN_ACTION)),
+            },
+            Some(Target::Variable(var)) => match action.name.as_str() {
+                // assignment
+                "" => {
+                    let arg = &action.args[0];
+                    if arg.value.get_type() != var.ptype {
+                        return Err(RuntimeError::new(arg.span, ERR_ASSIGN_TYPE));
+                    }
+                    let value = self.value_of(&arg.value, arg.span)?;
+                    *self.lookup_mut(var, action.span)? = value;
+                }
+                sp!("sin") => {
+                    Layout::fixed(&[]).check(&action.args, action.span)?;
+                    let scope = self
+                        .scopes
+                        .last_mut()
+                        .expect("there should always be a scope");
+                    if scope.contains_key(var) {
+                        return Err(RuntimeError::new(action.span, ERR_REDECLARED));
+                    }
+                    scope.insert(var.clone(), default_value(var.ptype));
+                }
+                _ => return Err(RuntimeError::new(action.span, ERR_UNKNOWN_ACTION)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Writes text to the console.
+    fn write(&mut self, text: &str, span: SrcSpan) -> RuntimeResult<()> {
+        self.console
+            .write(text)
+            .map_err(|err| RuntimeError::new(span, format!("{}: {}", ERR_WRITE, err)))
+    }
+
+    /// Finds a variable in the innermost scope that declares it.
+    fn lookup_mut(&mut self, var: &Variable, span: SrcSpan) -> RuntimeResult<&mut Value> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(var))
+            .ok_or_else(|| RuntimeError::new(span, ERR_UNDECLARED).with_help(HELP_UNDECLARED))
+    }
+
+    /// Gets the value of an object.
+    fn value_of(&self, obj: &Object, span: SrcSpan) -> RuntimeResult<Value> {
+        match obj {
+            Object::Literal(lit) => Ok(lit.clone()),
+            Object::Variable(var) => self
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(var))
+                .cloned()
+                .ok_or_else(|| RuntimeError::new(span, ERR_UNDECLARED).with_help(HELP_UNDECLARED)),
+            Object::Ni(ni) => match &self.ni {
+                Some(value) if value.get_type() == ni.ptype => Ok(value.clone()),
+                Some(value) => Err(RuntimeError::new(
+                    span,
+                    format!("ni holds a {} value, not {}", value.get_type(), ni.ptype),
+                )),
+                None => Err(RuntimeError::new(span, ERR_NI_EMPTY)),
+            },
+        }
+    }
+
+    /// Checks an optional condition, treating a missing condition as true.
+    fn check_condition(&self, cond: &Option<Condition>, span: SrcSpan) -> RuntimeResult<bool> {
+        match cond {
+            Some(cond) => self.eval_condition(cond, span),
+            None => Ok(true),
+        }
+    }
+
+    /// Evaluates a condition.
+    fn eval_condition(&self, cond: &Condition, span: SrcSpan) -> RuntimeResult<bool> {
+        let nanpa_pair = |a: &Object, b: &Object| -> RuntimeResult<(i64, i64)> {
+            match (self.value_of(a, span)?, self.value_of(b, span)?) {
+                (Literal::Nanpa(a), Literal::Nanpa(b)) => Ok((a, b)),
+                _ => Err(RuntimeError::new(span, ERR_COMPARE_NANPA)),
+            }
+        };
+        let lon_values = |ps: &[Object], q: &Object| -> RuntimeResult<(Vec<bool>, bool)> {
+            let lon = |obj: &Object| match self.value_of(obj, span)? {
+                Literal::Lon(value) => Ok(value),
+                _ => Err(RuntimeError::new(span, ERR_CHAIN_LON)),
+            };
+            let ps = ps.iter().map(lon).collect::<RuntimeResult<Vec<_>>>()?;
+            Ok((ps, lon(q)?))
+        };
+
+        match cond {
+            Condition::Equal(cond) => {
+                if cond.a.get_type() != cond.b.get_type() {
+                    return Err(RuntimeError::new(span, ERR_COMPARE_TYPES));
+                }
+                Ok(self.value_of(&cond.a, span)? == self.value_of(&cond.b, span)?)
+            }
+            Condition::Greater(cond) => {
+                let (a, b) = nanpa_pair(&cond.a, &cond.b)?;
+                Ok(a > b)
+            }
+            Condition::Less(cond) => {
+                let (a, b) = nanpa_pair(&cond.a, &cond.b)?;
+                Ok(a < b)
+            }
+            Condition::AndEqual(cond) => {
+                let (ps, q) = lon_values(&cond.ps, &cond.q)?;
+                Ok(ps.into_iter().all(|p| p) == q)
+            }
+            Condition::OrEqual(cond) => {
+                let (ps, q) = lon_values(&cond.ps, &cond.q)?;
+                Ok(ps.into_iter().any(|p| p) == q)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::object::{Literal, PrimitiveType, Variable},
+        parse::parse_program,
+        runtime::{Runtime, io::Streams},
+    };
+
+    type TestRuntime = Runtime<Streams<&'static [u8], Vec<u8>>>;
+
+    fn run_with_input(program: &str, input: &'static str) -> TestRuntime {
+        let stmts = parse_program(program).expect("program should parse");
+        let mut runtime = Runtime::with_console(Streams {
+            input: input.as_bytes(),
+            output: Vec::new(),
+        });
+        runtime.run(&stmts).expect("program should run");
+        runtime
+    }
+    fn run(program: &str) -> TestRuntime {
+        run_with_input(program, "")
+    }
+    fn run_invalid(program: &str) {
+        let stmts = parse_program(program).expect("program should parse");
+        let mut runtime = Runtime::with_console(Streams {
+            input: "".as_bytes(),
+            output: Vec::new(),
+        });
+        runtime.run(&stmts).expect_err("program should fail");
+    }
+    fn output(runtime: &TestRuntime) -> &str {
+        std::str::from_utf8(&runtime.console.output).expect("output should be UTF-8")
+    }
+    fn var(runtime: &TestRuntime, ptype: PrimitiveType, name: &str) -> Option<Literal> {
+        let var = Variable {
+            ptype,
+            name: name.into(),
+        };
+        runtime.scopes[0].get(&var).cloned()
+    }
+
+    #[test]
+    fn test_loop() {
+        let program = [
+            sp!("nanpa [ijo] o sin"),
+            sp!("nanpa [ijo] li lili tawa nanpa <luka luka> la o sike"),
+            sp!("o wan e nanpa [ijo] e nanpa <wan>"),
+            sp!("nanpa [ijo] o nanpa ni"),
+            sp!("nanpa [ijo] li nanpa <tu wan> la sike o sin"),
+            sp!("nanpa [ijo] li nanpa <luka tu> la sike o pini"),
+            sp!("pini"),
+        ]
+        .join("\n");
+        let runtime = run(&program);
+        assert_eq!(
+            var(&runtime, PrimitiveType::Nanpa, sp!("ijo")),
+            Some(Literal::Nanpa(7))
+        );
+    }
+
+    #[test]
+    fn test_ladder() {
+        let program = [
+            sp!("toki [ijo] o sin"),
+            sp!("lon [jan] o sin"),
+            sp!("lon [jan] o lon <lon>"),
+            sp!("lon <ala> en lon [jan] li lon <lon> la o pali"),
+            sp!("toki [ijo] o toki <wan>"),
+            sp!("ala la lon <ala> anu lon [jan] li lon <lon> la o pali"),
+            sp!("toki [ijo] o toki <tu>"),
+            sp!("ala la o pali"),
+            sp!("toki [ijo] o toki <mute>"),
+            sp!("pini"),
+        ]
+        .join("\n");
+        let runtime = run(&program);
+        assert_eq!(
+            var(&runtime, PrimitiveType::Toki, sp!("ijo")),
+            Some(Literal::Toki(sp!("tu").into()))
+        );
+    }
+
+    #[test]
+    fn test_ni() {
+        let program = [
+            sp!("o pana kipisi e nanpa <luka luka tu> kepeken nanpa <luka>"),
+            sp!("ken la nanpa ni li suli tawa nanpa <wan>"),
+        ]
+        .join("\n");
+        let runtime = run(&program);
+        assert_eq!(runtime.ni, Some(Literal::Lon(true)));
+    }
+
+    #[test]
+    fn test_multiple_actions() {
+        let program = [
+            sp!("o wan e nanpa <tu> e nanpa <tu> o kipisi e nanpa ni tawa nanpa <tu>"),
+            sp!("nanpa [ijo] o sin o nanpa ni o nanpa <luka>"),
+        ]
+        .join("\n");
+        let runtime = run(&program);
+        assert_eq!(runtime.ni, Some(Literal::Nanpa(2)));
+        assert_eq!(
+            var(&runtime, PrimitiveType::Nanpa, sp!("ijo")),
+            Some(Literal::Nanpa(5))
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        let program = [
+            sp!("o pali"),
+            sp!("nanpa [ijo] o sin"),
+            sp!("pini"),
+            sp!("nanpa [ijo] o sin"),
+        ]
+        .join("\n");
+        let runtime = run(&program);
+        assert_eq!(
+            var(&runtime, PrimitiveType::Nanpa, sp!("ijo")),
+            Some(Literal::Nanpa(0))
+        );
+    }
+
+    #[test]
+    fn test_console() {
+        let program = [
+            sp!("ilo o wile linja"),
+            sp!("ilo o toki e toki <jan> e toki ni e nanpa <mute tu>"),
+            sp!("ilo o pini linja"),
+            sp!("ilo o wile linja"),
+            sp!("ilo o wile linja"),
+            sp!("ilo o toki e toki ni e lon <ala>"),
+        ]
+        .join("\n");
+        let runtime = run_with_input(&program, concat!(sp!("pona"), "\r\n"));
+        assert_eq!(
+            output(&runtime),
+            concat!(sp!("jan pona mute tu"), "\n", sp!("ala"))
+        );
+        assert_eq!(runtime.ni, Some(Literal::Toki(String::new())));
+    }
+
+    #[test]
+    fn test_example() {
+        let program = include_str!("../../../examples/musi_nanpa.lipu");
+        let runtime = run(program);
+        let lines: Vec<_> = output(&runtime).lines().collect();
+        assert_eq!(lines.len(), 99);
+        assert_eq!(lines[0], sp!("wan"));
+        assert_eq!(lines[2], sp!("loje"));
+        assert_eq!(lines[4], sp!("jelo"));
+        assert_eq!(lines[14], sp!("loje jelo"));
+        assert_eq!(lines[97], sp!("mute mute mute mute luka luka luka tu wan"));
+    }
+
+    #[test]
+    fn test_failures() {
+        run_invalid(sp!("nanpa [ijo] o nanpa <wan>"));
+        run_invalid(&[sp!("nanpa [ijo] o sin"), sp!("nanpa [ijo] o sin")].join("\n"));
+        run_invalid(
+            &[
+                sp!("o pali"),
+                sp!("nanpa [ijo] o sin"),
+                sp!("pini"),
+                sp!("nanpa [ijo] o nanpa <wan>"),
+            ]
+            .join("\n"),
+        );
+        run_invalid(sp!("ken la nanpa ni li nanpa <wan>"));
+        run_invalid(
+            &[
+                sp!("ken la nanpa <wan> li nanpa <wan>"),
+                sp!("o sona e nanpa ni"),
+            ]
+            .join("\n"),
+        );
+        run_invalid(sp!("o pana kipisi e nanpa <wan> kepeken nanpa <ala>"));
+        run_invalid(sp!("o pana kipisi e nanpa <wan> tawa nanpa <wan>"));
+        run_invalid(sp!("o moku e nanpa <wan>"));
+        run_invalid(sp!("nanpa [ijo] o nanpa <wan> o sin"));
+    }
+}
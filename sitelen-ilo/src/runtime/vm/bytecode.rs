@@ -0,0 +1,70 @@
+//! Bytecode run by the virtual machine.
+
+use crate::{
+    ast::{SrcSpan, object::PrimitiveType},
+    runtime::{Value, error::RuntimeError},
+};
+
+/// Index of a variable slot. Names are resolved to slots when a program is compiled.
+pub(crate) type Slot = u32;
+/// Index of an instruction in a [`Chunk`].
+pub(crate) type Label = u32;
+
+/// One instruction. Values are passed on a stack, and each instruction has a span in the source
+/// that errors are reported at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    /// Pushes a value from [`Chunk::constants`].
+    Const(u32),
+    /// Gives a variable the default value for its type. Fails if it is already declared.
+    Declare(Slot, PrimitiveType),
+    /// Pushes the value of a variable. Fails if it isn't declared.
+    Load(Slot),
+    /// Pops a value into a variable. Fails if it isn't declared.
+    Store(Slot),
+    /// Undeclares the variables in a range of slots, when a block ends.
+    Forget(Slot, Slot),
+    /// Pushes the value of *ni*, which must have the given type.
+    LoadNi(PrimitiveType),
+    /// Pops a value into *ni*.
+    StoreNi,
+    /// Pops arguments and runs the intrinsic with the given index, saving the result to *ni*.
+    Intrinsic(u16, u16),
+    /// Pops values and writes them to the console.
+    Write(u16),
+    /// Writes a line break to the console.
+    WriteLine,
+    /// Reads a line from the console into *ni*.
+    ReadLine,
+    /// Pops two values and pushes whether they are equal.
+    Equal,
+    /// Pops two *nanpa* values and pushes whether the first is greater.
+    Greater,
+    /// Pops two *nanpa* values and pushes whether the first is less.
+    Less,
+    /// Pops *lon* values and then one more, and pushes whether the last one is equal to all of the
+    /// others together.
+    AndEqual(u16),
+    /// Pops *lon* values and then one more, and pushes whether the last one is equal to any of the
+    /// others.
+    OrEqual(u16),
+    /// Continues at another instruction.
+    Jump(Label),
+    /// Pops a *lon* value, and continues at another instruction if it is false.
+    JumpIfFalse(Label),
+    /// Fails with an error from [`Chunk::errors`], found while compiling.
+    Fail(u32),
+}
+
+/// A compiled program.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Chunk {
+    pub(crate) ops: Vec<Op>,
+    /// The span of each instruction.
+    pub(crate) spans: Vec<SrcSpan>,
+    pub(crate) constants: Vec<Value>,
+    /// Errors that are raised if the code containing them runs.
+    pub(crate) errors: Vec<RuntimeError>,
+    /// Number of variable slots used.
+    pub(crate) slots: u32,
+}
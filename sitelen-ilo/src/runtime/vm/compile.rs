@@ -0,0 +1,437 @@
+//! Compilation of programs to bytecode.
+//!
+//! Mistakes that the tree-walking interpreter reports while running, such as unknown actions, are
+//! compiled to [`Op::Fail`] so that they are reported at the same point in the program.
+
+use std::collections::HashMap;
+
+use sitelen_ilo_macros::sp;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        condition::Condition,
+        object::{Object, PrimitiveType, TypedValue, Variable},
+        statement::{Action, ArgKey, Argument, BlockStart, LoopControlKind, Target},
+    },
+    runtime::{
+        ERR_ASSIGN_TYPE, ERR_CHAIN_LON, ERR_COMPARE_NANPA, ERR_COMPARE_TYPES, ERR_UNDECLARED,
+        ERR_UNKNOWN_ACTION, HELP_UNDECLARED,
+        error::{RuntimeError, RuntimeResult},
+        intrinsic::{self, Layout},
+        vm::bytecode::{Chunk, Label, Op, Slot},
+    },
+};
+
+/// Placeholder for jumps whose target isn't known yet.
+const UNPATCHED: Label = Label::MAX;
+
+/// Variables declared in a block.
+struct Scope {
+    vars: HashMap<Variable, Slot>,
+    /// The first slot used by the block. Slots are reused once a block ends.
+    start: Slot,
+}
+
+/// A loop being compiled.
+struct Loop {
+    /// Where the condition is checked.
+    start: Label,
+    /// Jumps to patch with the end of the loop.
+    breaks: Vec<usize>,
+    /// Index of the scope of the loop's body.
+    scope: usize,
+}
+
+struct Compiler {
+    chunk: Chunk,
+    /// Scopes from outermost to innermost.
+    scopes: Vec<Scope>,
+    /// The next unused slot.
+    next_slot: Slot,
+    /// Loops from outermost to innermost.
+    loops: Vec<Loop>,
+    /// Jumps to patch with the end of the program, from loop control outside of loops.
+    exits: Vec<usize>,
+}
+impl Compiler {
+    fn emit(&mut self, op: Op, span: SrcSpan) -> usize {
+        self.chunk.ops.push(op);
+        self.chunk.spans.push(span);
+        self.chunk.ops.len() - 1
+    }
+
+    /// Gets the label of the next instruction.
+    fn here(&self) -> Label {
+        self.chunk.ops.len() as Label
+    }
+
+    /// Points a jump at the next instruction.
+    fn patch(&mut self, jump: usize) {
+        let here = self.here();
+        match &mut self.chunk.ops[jump] {
+            Op::Jump(target) | Op::JumpIfFalse(target) => *target = here,
+            op => unreachable!("{:?} is not a jump", op),
+        }
+    }
+
+    fn fail(&mut self, err: RuntimeError) {
+        let span = err.span();
+        self.chunk.errors.push(err);
+        self.emit(Op::Fail(self.chunk.errors.len() as u32 - 1), span);
+    }
+
+    /// Emits a failure if a layout check fails, returning whether it passed.
+    fn check(&mut self, result: RuntimeResult<()>) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                self.fail(err);
+                false
+            }
+        }
+    }
+
+    fn undeclared(&mut self, span: SrcSpan) {
+        self.fail(RuntimeError::new(span, ERR_UNDECLARED).with_help(HELP_UNDECLARED));
+    }
+
+    /// Finds the slot of a variable in the innermost scope that declares it.
+    fn resolve(&self, var: &Variable) -> Option<Slot> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.vars.get(var).copied())
+    }
+
+    fn open_scope(&mut self) {
+        self.scopes.push(Scope {
+            vars: HashMap::new(),
+            start: self.next_slot,
+        });
+    }
+
+    fn close_scope(&mut self, span: SrcSpan) {
+        let scope = self.scopes.pop().expect("there should be a scope to close");
+        self.forget(scope.start, span);
+        self.next_slot = scope.start;
+    }
+
+    /// Undeclares every variable from `start` onwards.
+    fn forget(&mut self, start: Slot, span: SrcSpan) {
+        if start < self.next_slot {
+            self.emit(Op::Forget(start, self.next_slot), span);
+        }
+    }
+
+    /// Pushes the value of an object.
+    fn object(&mut self, obj: &Object, span: SrcSpan) {
+        match obj {
+            Object::Literal(lit) => {
+                self.chunk.constants.push(lit.clone());
+                let index = self.chunk.constants.len() as u32 - 1;
+                self.emit(Op::Const(index), span);
+            }
+            Object::Variable(var) => match self.resolve(var) {
+                Some(slot) => {
+                    self.emit(Op::Load(slot), span);
+                }
+                None => self.undeclared(span),
+            },
+            Object::Ni(ni) => {
+                self.emit(Op::LoadNi(ni.ptype), span);
+            }
+        }
+    }
+
+    /// Pushes the values of some arguments.
+    fn arguments(&mut self, args: &[Argument]) {
+        for arg in args {
+            self.object(&arg.value, arg.span);
+        }
+    }
+
+    /// Pushes two *nanpa* values to compare, failing if either isn't *nanpa*.
+    fn nanpa_pair(&mut self, a: &Object, b: &Object, span: SrcSpan) {
+        self.object(a, span);
+        self.object(b, span);
+        if a.get_type() != PrimitiveType::Nanpa || b.get_type() != PrimitiveType::Nanpa {
+            self.fail(RuntimeError::new(span, ERR_COMPARE_NANPA));
+        }
+    }
+
+    /// Pushes *lon* values for a chain, failing at the first one that isn't *lon*.
+    fn lon_chain(&mut self, ps: &[Object], q: &Object, span: SrcSpan) {
+        for obj in ps.iter().chain([q]) {
+            self.object(obj, span);
+            if obj.get_type() != PrimitiveType::Lon {
+                self.fail(RuntimeError::new(span, ERR_CHAIN_LON));
+            }
+        }
+    }
+
+    /// Pushes whether a condition holds.
+    fn condition(&mut self, cond: &Condition, span: SrcSpan) {
+        match cond {
+            Condition::Equal(cond) => {
+                if cond.a.get_type() != cond.b.get_type() {
+                    self.fail(RuntimeError::new(span, ERR_COMPARE_TYPES));
+                }
+                self.object(&cond.a, span);
+                self.object(&cond.b, span);
+                self.emit(Op::Equal, span);
+            }
+            Condition::Greater(cond) => {
+                self.nanpa_pair(&cond.a, &cond.b, span);
+                self.emit(Op::Greater, span);
+            }
+            Condition::Less(cond) => {
+                self.nanpa_pair(&cond.a, &cond.b, span);
+                self.emit(Op::Less, span);
+            }
+            Condition::AndEqual(cond) => {
+                self.lon_chain(&cond.ps, &cond.q, span);
+                self.emit(Op::AndEqual(cond.ps.len() as u16), span);
+            }
+            Condition::OrEqual(cond) => {
+                self.lon_chain(&cond.ps, &cond.q, span);
+                self.emit(Op::OrEqual(cond.ps.len() as u16), span);
+            }
+        }
+    }
+
+    /// Emits a jump past the following code if a condition is false. A missing condition is true.
+    fn skip_unless(&mut self, cond: &Option<Condition>, span: SrcSpan) -> Option<usize> {
+        let cond = cond.as_ref()?;
+        self.condition(cond, span);
+        Some(self.emit(Op::JumpIfFalse(UNPATCHED), span))
+    }
+
+    fn action(&mut self, target: &Option<Target>, action: &Action) {
+        let span = action.span;
+        let args = &action.args;
+        match target {
+            None => {
+                let Some(index) = intrinsic::position(&action.name) else {
+                    return self.fail(RuntimeError::new(span, ERR_UNKNOWN_ACTION));
+                };
+                let layout = &intrinsic::by_index(index).layout;
+                if self.check(layout.check(args, span)) {
+                    self.arguments(args);
+                    self.emit(Op::Intrinsic(index as u16, args.len() as u16), span);
+                }
+            }
+            Some(Target::Ilo) => match action.name.as_str() {
+                sp!("toki") => {
+                    let layout = Layout::repeated((ArgKey::E, None));
+                    if self.check(layout.check(args, span)) {
+                        self.arguments(args);
+                        self.emit(Op::Write(args.len() as u16), span);
+                    }
+                }
+                sp!("pini linja") => {
+                    if self.check(Layout::fixed(&[]).check(args, span)) {
+                        self.emit(Op::WriteLine, span);
+                    }
+                }
+                sp!("wile linja") => {
+                    if self.check(Layout::fixed(&[]).check(args, span)) {
+                        self.emit(Op::ReadLine, span);
+                    }
+                }
+                _ => self.fail(RuntimeError::new(span, ERR_UNKNOWN_ACTION)),
+            },
+            Some(Target::Variable(var)) => match action.name.as_str() {
+                // assignment
+                "" => {
+                    let arg = &args[0];
+                    if arg.value.get_type() != var.ptype {
+                        return self.fail(RuntimeError::new(arg.span, ERR_ASSIGN_TYPE));
+                    }
+                    self.object(&arg.value, arg.span);
+                    match self.resolve(var) {
+                        Some(slot) => {
+                            self.emit(Op::Store(slot), span);
+                        }
+                        None => self.undeclared(span),
+                    }
+                }
+                sp!("sin") => {
+                    if !self.check(Layout::fixed(&[]).check(args, span)) {
+                        return;
+                    }
+                    let scope = self
+                        .scopes
+                        .last_mut()
+                        .expect("there should always be a scope");
+                    let slot = match scope.vars.get(var) {
+                        // declaring it again fails when it runs
+                        Some(&slot) => slot,
+                        None => {
+                            let slot = self.next_slot;
+                            scope.vars.insert(var.clone(), slot);
+                            self.next_slot += 1;
+                            self.chunk.slots = self.chunk.slots.max(self.next_slot);
+                            slot
+                        }
+                    };
+                    self.emit(Op::Declare(slot, var.ptype), span);
+                }
+                _ => self.fail(RuntimeError::new(span, ERR_UNKNOWN_ACTION)),
+            },
+        }
+    }
+
+    /// Compiles the body of a block in a new scope.
+    fn block(&mut self, stmts: &[Statement], span: SrcSpan) {
+        self.open_scope();
+        self.statements(stmts);
+        self.close_scope(span);
+    }
+
+    fn loop_block(&mut self, start: &BlockStart, contents: &[Statement]) {
+        let label = self.here();
+        let skip = self.skip_unless(&start.condition, start.span);
+        self.loops.push(Loop {
+            start: label,
+            breaks: Vec::new(),
+            scope: self.scopes.len(),
+        });
+        self.block(contents, start.span);
+        self.emit(Op::Jump(label), start.span);
+
+        let lp = self.loops.pop().expect("loop should still be open");
+        for jump in skip.into_iter().chain(lp.breaks) {
+            self.patch(jump);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Comment(_) => (),
+            Statement::Command(cmd) => {
+                let skip = self.skip_unless(&cmd.condition, cmd.span);
+                for action in &cmd.actions {
+                    self.action(&cmd.target, action);
+                }
+                if let Some(skip) = skip {
+                    self.patch(skip);
+                }
+            }
+            Statement::ConditionEval(eval) => {
+                self.condition(&eval.condition, eval.span);
+                self.emit(Op::StoreNi, eval.span);
+            }
+            Statement::IfLadder(ladder) => {
+                let mut ends = Vec::new();
+                for block in &ladder.blocks {
+                    let skip = self.skip_unless(&block.start.condition, block.start.span);
+                    self.block(&block.contents, block.start.span);
+                    ends.push(self.emit(Op::Jump(UNPATCHED), block.start.span));
+                    if let Some(skip) = skip {
+                        self.patch(skip);
+                    }
+                }
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+            Statement::LoopBlock(block) => self.loop_block(&block.start, &block.contents),
+            Statement::LoopControl(control) => {
+                let skip = self.skip_unless(&control.condition, control.span);
+                match self.loops.last() {
+                    Some(lp) => {
+                        let (start, scope) = (lp.start, lp.scope);
+                        self.forget(self.scopes[scope].start, control.span);
+                        match control.kind {
+                            LoopControlKind::Pini => {
+                                let jump = self.emit(Op::Jump(UNPATCHED), control.span);
+                                let lp = self.loops.last_mut().expect("loop should be open");
+                                lp.breaks.push(jump);
+                            }
+                            LoopControlKind::Sin => {
+                                self.emit(Op::Jump(start), control.span);
+                            }
+                        }
+                    }
+                    // outside of a loop, the program ends
+                    None => {
+                        let jump = self.emit(Op::Jump(UNPATCHED), control.span);
+                        self.exits.push(jump);
+                    }
+                }
+                if let Some(skip) = skip {
+                    self.patch(skip);
+                }
+            }
+        }
+    }
+
+    fn statements(&mut self, stmts: &[Statement]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+}
+
+/// Compiles a program to bytecode.
+pub(crate) fn compile(program: &[Statement]) -> Chunk {
+    let mut compiler = Compiler {
+        chunk: Chunk::default(),
+        scopes: vec![Scope {
+            vars: HashMap::new(),
+            start: 0,
+        }],
+        next_slot: 0,
+        loops: Vec::new(),
+        exits: Vec::new(),
+    };
+    compiler.statements(program);
+    for exit in std::mem::take(&mut compiler.exits) {
+        compiler.patch(exit);
+    }
+    compiler.chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        ast::object::PrimitiveType,
+        parse::parse_program,
+        runtime::vm::{bytecode::Op, compile::compile},
+    };
+
+    #[test]
+    fn test_slots() {
+        let program = [
+            sp!("nanpa [ijo] o sin"),
+            sp!("o sike"),
+            sp!("toki [ijo] o sin"),
+            sp!("nanpa [ijo] o nanpa <wan>"),
+            sp!("toki [ijo] o toki ni"),
+            sp!("sike o pini"),
+            sp!("pini"),
+        ]
+        .join("\n");
+        let chunk = compile(&parse_program(&program).unwrap());
+        assert_eq!(
+            chunk.ops,
+            [
+                Op::Declare(0, PrimitiveType::Nanpa),
+                Op::Declare(1, PrimitiveType::Toki),
+                Op::Const(0),
+                Op::Store(0),
+                Op::LoadNi(PrimitiveType::Toki),
+                Op::Store(1),
+                Op::Forget(1, 2),
+                Op::Jump(10),
+                Op::Forget(1, 2),
+                Op::Jump(1),
+            ]
+        );
+        assert_eq!(chunk.slots, 2);
+    }
+}
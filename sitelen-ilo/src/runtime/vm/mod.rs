@@ -0,0 +1,339 @@
+//! Stack-based virtual machine, an alternative to the tree-walking interpreter.
+
+use crate::{
+    ast::{
+        SrcSpan,
+        object::{Literal, TypedValue},
+    },
+    runtime::{
+        ERR_NI_EMPTY, ERR_READ, ERR_REDECLARED, ERR_UNDECLARED, ERR_WRITE, HELP_UNDECLARED, Value,
+        default_value, display_value,
+        error::{RuntimeError, RuntimeResult},
+        intrinsic::{self, Operand},
+        io::{Console, Stdio},
+        vm::bytecode::{Chunk, Op},
+    },
+};
+
+pub(crate) mod bytecode;
+pub(crate) mod compile;
+
+pub(crate) use compile::compile;
+
+/// State of a running program.
+pub(crate) struct Vm<C = Stdio> {
+    /// The value of each variable slot, or `None` if it isn't declared.
+    slots: Vec<Option<Value>>,
+    /// Values being passed between instructions.
+    stack: Vec<Operand>,
+    /// The last result of an operation.
+    ni: Option<Value>,
+    /// Where *ilo* reads and writes text.
+    console: C,
+}
+impl Vm {
+    /// Creates a new virtual machine connected to stdin and stdout.
+    pub(crate) fn new() -> Self {
+        Self::with_console(Stdio::stdio())
+    }
+}
+impl<C: Console> Vm<C> {
+    /// Creates a new virtual machine using a console.
+    pub(crate) fn with_console(console: C) -> Self {
+        Self {
+            slots: Vec::new(),
+            stack: Vec::new(),
+            ni: None,
+            console,
+        }
+    }
+
+    fn push(&mut self, value: Value, span: SrcSpan) {
+        self.stack.push(Operand { value, span });
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack should not be empty").value
+    }
+
+    /// Pops a *lon* value. The compiler ensures that the value is *lon*.
+    fn pop_lon(&mut self) -> bool {
+        match self.pop() {
+            Literal::Lon(value) => value,
+            value => unreachable!("expected lon, found {:?}", value),
+        }
+    }
+
+    /// Pops two *nanpa* values. The compiler ensures that the values are *nanpa*.
+    fn pop_nanpa_pair(&mut self) -> (i64, i64) {
+        match (self.pop(), self.pop()) {
+            (Literal::Nanpa(b), Literal::Nanpa(a)) => (a, b),
+            values => unreachable!("expected nanpa, found {:?}", values),
+        }
+    }
+
+    /// Pops `count` values, in the order they were pushed.
+    fn pop_many(&mut self, count: u16) -> Vec<Operand> {
+        self.stack.split_off(self.stack.len() - count as usize)
+    }
+
+    fn write(&mut self, text: &str, span: SrcSpan) -> RuntimeResult<()> {
+        self.console
+            .write(text)
+            .map_err(|err| RuntimeError::new(span, format!("{}: {}", ERR_WRITE, err)))
+    }
+
+    fn slot_mut(&mut self, slot: u32, span: SrcSpan) -> RuntimeResult<&mut Value> {
+        self.slots[slot as usize]
+            .as_mut()
+            .ok_or_else(|| RuntimeError::new(span, ERR_UNDECLARED).with_help(HELP_UNDECLARED))
+    }
+
+    /// Runs a compiled program.
+    pub(crate) fn run(&mut self, chunk: &Chunk) -> RuntimeResult<()> {
+        self.slots = vec![None; chunk.slots as usize];
+        self.stack.clear();
+
+        let mut pc = 0;
+        while let Some(&op) = chunk.ops.get(pc) {
+            let span = chunk.spans[pc];
+            pc += 1;
+            match op {
+                Op::Const(index) => self.push(chunk.constants[index as usize].clone(), span),
+                Op::Declare(slot, ptype) => {
+                    let slot = &mut self.slots[slot as usize];
+                    if slot.is_some() {
+                        return Err(RuntimeError::new(span, ERR_REDECLARED));
+                    }
+                    *slot = Some(default_value(ptype));
+                }
+                Op::Load(slot) => {
+                    let value = self.slot_mut(slot, span)?.clone();
+                    self.push(value, span);
+                }
+                Op::Store(slot) => {
+                    let value = self.pop();
+                    *self.slot_mut(slot, span)? = value;
+                }
+                Op::Forget(start, end) => {
+                    self.slots[start as usize..end as usize].fill(None);
+                }
+                Op::LoadNi(ptype) => match &self.ni {
+                    Some(value) if value.get_type() == ptype => self.push(value.clone(), span),
+                    Some(value) => {
+                        return Err(RuntimeError::new(
+                            span,
+                            format!("ni holds a {} value, not {}", value.get_type(), ptype),
+                        ));
+                    }
+                    None => return Err(RuntimeError::new(span, ERR_NI_EMPTY)),
+                },
+                Op::StoreNi => self.ni = Some(self.pop()),
+                Op::Intrinsic(index, count) => {
+                    let operands = self.pop_many(count);
+                    self.ni = Some((intrinsic::by_index(index as usize).run)(&operands)?);
+                }
+                Op::Write(count) => {
+                    let text: String = self
+                        .pop_many(count)
+                        .iter()
+                        .map(|operand| display_value(&operand.value))
+                        .collect();
+                    self.write(&text, span)?;
+                }
+                Op::WriteLine => self.write("\n", span)?,
+                Op::ReadLine => {
+                    let line = self
+                        .console
+                        .read_line()
+                        .map_err(|err| RuntimeError::new(span, format!("{}: {}", ERR_READ, err)))?;
+                    // the end of input reads as an empty line
+                    self.ni = Some(Literal::Toki(line.unwrap_or_default()));
+                }
+                Op::Equal => {
+                    let (b, a) = (self.pop(), self.pop());
+                    self.push(Literal::Lon(a == b), span);
+                }
+                Op::Greater => {
+                    let (a, b) = self.pop_nanpa_pair();
+                    self.push(Literal::Lon(a > b), span);
+                }
+                Op::Less => {
+                    let (a, b) = self.pop_nanpa_pair();
+                    self.push(Literal::Lon(a < b), span);
+                }
+                Op::AndEqual(count) | Op::OrEqual(count) => {
+                    let q = self.pop_lon();
+                    let ps = self.pop_many(count);
+                    let mut ps = ps.iter().map(|operand| operand.value == Literal::Lon(true));
+                    let p = match op {
+                        Op::AndEqual(_) => ps.all(|p| p),
+                        _ => ps.any(|p| p),
+                    };
+                    self.push(Literal::Lon(p == q), span);
+                }
+                Op::Jump(label) => pc = label as usize,
+                Op::JumpIfFalse(label) => {
+                    if !self.pop_lon() {
+                        pc = label as usize;
+                    }
+                }
+                Op::Fail(index) => return Err(chunk.errors[index as usize].clone()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sitelen_ilo_macros::{sp, sp_c};
+
+    use crate::{
+        ast::object::TypedValue,
+        parse::parse_program,
+        runtime::{
+            Runtime, Value, display_value,
+            error::RuntimeError,
+            io::Streams,
+            vm::{Vm, compile},
+        },
+    };
+
+    /// Describes a value, with its type, so that the two backends can be compared.
+    fn describe(value: &Option<Value>) -> String {
+        match value {
+            Some(value) => format!("{}{}", value.get_type(), display_value(value)),
+            None => sp_c!("ala").to_string(),
+        }
+    }
+
+    /// Runs a program with both backends, checking that they print the same output, leave *ni*
+    /// with the same value, and fail with the same error.
+    fn cross_check(program: &str, input: &'static str) {
+        let stmts = parse_program(program).expect("program should parse");
+
+        let mut runtime = Runtime::with_console(Streams {
+            input: input.as_bytes(),
+            output: Vec::new(),
+        });
+        let tree_result = runtime.run(&stmts);
+
+        let mut vm = Vm::with_console(Streams {
+            input: input.as_bytes(),
+            output: Vec::new(),
+        });
+        let vm_result = vm.run(&compile(&stmts));
+
+        let describe_err = |result: Result<(), _>| {
+            result.map_err(|err: RuntimeError| {
+                let located = err.locate(program);
+                (located.span().location_offset(), err.to_string())
+            })
+        };
+        assert_eq!(describe_err(vm_result), describe_err(tree_result));
+        assert_eq!(vm.console.output, runtime.console.output);
+        assert_eq!(describe(&vm.ni), describe(&runtime.ni));
+    }
+
+    #[test]
+    fn test_example() {
+        cross_check(include_str!("../../../../examples/musi_nanpa.lipu"), "");
+    }
+
+    #[test]
+    fn test_corpus() {
+        let programs = [
+            // loops, with skipping and breaking
+            [
+                sp!("nanpa [ijo] o sin"),
+                sp!("nanpa [ijo] li lili tawa nanpa <luka luka> la o sike"),
+                sp!("o wan e nanpa [ijo] e nanpa <wan>"),
+                sp!("nanpa [ijo] o nanpa ni"),
+                sp!("nanpa [ijo] li nanpa <tu wan> la sike o sin"),
+                sp!("o pali"),
+                sp!("nanpa [ijo] li nanpa <luka tu> la sike o pini"),
+                sp!("pini"),
+                sp!("ilo o toki e nanpa [ijo]"),
+                sp!("pini"),
+            ]
+            .join("\n"),
+            // ladders and chains
+            [
+                sp!("toki [ijo] o sin"),
+                sp!("lon [jan] o sin"),
+                sp!("lon [jan] o lon <lon>"),
+                sp!("lon <ala> en lon [jan] li lon <lon> la o pali"),
+                sp!("toki [ijo] o toki <wan>"),
+                sp!("ala la lon <ala> anu lon [jan] li lon <lon> la o pali"),
+                sp!("toki [ijo] o toki <tu>"),
+                sp!("ala la o pali"),
+                sp!("toki [ijo] o toki <mute>"),
+                sp!("pini"),
+                sp!("ilo o toki e toki [ijo]"),
+                sp!("ken la toki [ijo] li toki <tu>"),
+            ]
+            .join("\n"),
+            // scopes, which are forgotten each time around a loop
+            [
+                sp!("nanpa [ijo] o sin"),
+                sp!("nanpa [ijo] li lili tawa nanpa <tu> la o sike"),
+                sp!("nanpa [jan] o sin o nanpa <luka>"),
+                sp!("o wan e nanpa [ijo] e nanpa <wan>"),
+                sp!("nanpa [ijo] o nanpa ni"),
+                sp!("ilo o toki e nanpa [jan] e nanpa [ijo]"),
+                sp!("ilo o pini linja"),
+                sp!("pini"),
+            ]
+            .join("\n"),
+            // console input
+            [
+                sp!("ilo o wile linja"),
+                sp!("ilo o toki e toki <jan> e toki ni e nanpa <mute tu>"),
+                sp!("ilo o wile linja"),
+                sp!("ilo o wile linja"),
+            ]
+            .join("\n"),
+        ];
+        for program in &programs {
+            cross_check(program, concat!(sp!("pona"), "\n"));
+        }
+    }
+
+    #[test]
+    fn test_failures() {
+        let programs = [
+            sp!("nanpa [ijo] o nanpa <wan>").to_string(),
+            [sp!("nanpa [ijo] o sin"), sp!("nanpa [ijo] o sin")].join("\n"),
+            [
+                sp!("o pali"),
+                sp!("nanpa [ijo] o sin"),
+                sp!("pini"),
+                sp!("nanpa [ijo] o nanpa <wan>"),
+            ]
+            .join("\n"),
+            sp!("ken la nanpa ni li nanpa <wan>").to_string(),
+            [
+                sp!("ken la nanpa <wan> li nanpa <wan>"),
+                sp!("o sona e nanpa ni"),
+            ]
+            .join("\n"),
+            sp!("o pana kipisi e nanpa <wan> kepeken nanpa <ala>").to_string(),
+            sp!("o pana kipisi e nanpa <wan> tawa nanpa <wan>").to_string(),
+            sp!("o moku e nanpa <wan>").to_string(),
+            sp!("nanpa [ijo] o nanpa <wan> o sin").to_string(),
+            sp!("ken la toki <wan> li suli tawa nanpa ni").to_string(),
+            sp!("ken la lon <lon> en nanpa <wan> li lon ni").to_string(),
+            sp!("nanpa [ijo] o sin o toki <wan>").to_string(),
+            sp!("ilo o toki e toki <wan> o moku").to_string(),
+            [
+                sp!("ilo o toki e toki <wan>"),
+                sp!("ilo o pini linja e toki <tu>"),
+            ]
+            .join("\n"),
+        ];
+        for program in &programs {
+            cross_check(program, "");
+        }
+    }
+}
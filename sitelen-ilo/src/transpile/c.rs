@@ -0,0 +1,595 @@
+//! Compiling programs to a single portable C99 file.
+//!
+//! Programs are lowered through the same bytecode as the virtual machine, so that names are
+//! resolved and mistakes are reported in the same way. Each instruction becomes a line of C that
+//! calls into a small runtime, which is copied into the output.
+
+use std::fmt::Write;
+
+use sitelen_ilo_lasina::Transliteration;
+use sitelen_ilo_macros::sp_c;
+
+use crate::{
+    ast::{
+        SrcSpan,
+        block::Statement,
+        object::{Literal, PrimitiveType},
+    },
+    parse::object::{ERR_FAILED_LON_MATCH, ERR_NNP_FAILED_MATCH, ERR_NNP_TOO_LARGE},
+    runtime::{
+        ERR_NI_EMPTY, ERR_READ, ERR_REDECLARED, ERR_UNDECLARED, ERR_WRITE, HELP_UNDECLARED,
+        intrinsic::{
+            self, ERR_CODEPOINT, ERR_DIV_ZERO, ERR_INDEX_RANGE, ERR_NOT_CHAR, ERR_OVERFLOW,
+            ERR_SUBSTR_ORDER,
+        },
+        vm::{
+            self,
+            bytecode::{Chunk, Label, Op},
+        },
+    },
+};
+
+/// The runtime, which expects the definitions from [`Emitter::definitions`] before it.
+const RUNTIME: &str = include_str!("runtime.c");
+
+/// Messages used by the runtime, so that they match the interpreter.
+const MESSAGES: &[(&str, &str)] = &[
+    ("ILO_ERR_UNDECLARED", ERR_UNDECLARED),
+    ("ILO_HELP_UNDECLARED", HELP_UNDECLARED),
+    ("ILO_ERR_REDECLARED", ERR_REDECLARED),
+    ("ILO_ERR_NI_EMPTY", ERR_NI_EMPTY),
+    ("ILO_ERR_WRITE", ERR_WRITE),
+    ("ILO_ERR_READ", ERR_READ),
+    ("ILO_ERR_OVERFLOW", ERR_OVERFLOW),
+    ("ILO_ERR_DIV_ZERO", ERR_DIV_ZERO),
+    ("ILO_ERR_INDEX_RANGE", ERR_INDEX_RANGE),
+    ("ILO_ERR_SUBSTR_ORDER", ERR_SUBSTR_ORDER),
+    ("ILO_ERR_NOT_CHAR", ERR_NOT_CHAR),
+    ("ILO_ERR_CODEPOINT", ERR_CODEPOINT),
+    ("ILO_ERR_NNP_TOO_LARGE", ERR_NNP_TOO_LARGE),
+    ("ILO_ERR_NNP_FAILED_MATCH", ERR_NNP_FAILED_MATCH),
+    ("ILO_ERR_FAILED_LON_MATCH", ERR_FAILED_LON_MATCH),
+];
+
+/// Words the runtime reads and writes values with.
+const WORDS: &[(&str, char)] = &[
+    ("ILO_WORD_ALA", sp_c!("ala")),
+    ("ILO_WORD_ALE", sp_c!("ale")),
+    ("ILO_WORD_MUTE", sp_c!("mute")),
+    ("ILO_WORD_LUKA", sp_c!("luka")),
+    ("ILO_WORD_TU", sp_c!("tu")),
+    ("ILO_WORD_WAN", sp_c!("wan")),
+    ("ILO_WORD_WEKA", sp_c!("weka")),
+    ("ILO_WORD_LON", sp_c!("lon")),
+];
+
+/// Writes text as a C string literal. Bytes outside of printable ASCII are written as octal
+/// escapes, which unlike hex escapes can't run into the characters after them.
+fn c_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for &byte in text.as_bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            // `??` can start a trigraph
+            b'?' => out.push_str("\\?"),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn c_type(ptype: PrimitiveType) -> &'static str {
+    match ptype {
+        PrimitiveType::Toki => "ILO_TOKI",
+        PrimitiveType::Nanpa => "ILO_NANPA",
+        PrimitiveType::Lon => "ILO_LON",
+    }
+}
+
+fn c_literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Toki(text) => format!("ilo_toki_new({}, {})", c_string(text), text.len()),
+        // the smallest nanpa can't be written as a literal
+        Literal::Nanpa(i64::MIN) => "ilo_nanpa_new(INT64_MIN)".to_string(),
+        Literal::Nanpa(value) => format!("ilo_nanpa_new(INT64_C({}))", value),
+        Literal::Lon(value) => format!("ilo_lon_new({})", value),
+    }
+}
+
+/// Gets the name of the runtime function for an intrinsic, from its name in sitelen Lasina.
+fn c_intrinsic(index: u16) -> String {
+    let name = sitelen_ilo_lasina::to_lasina(intrinsic::action(index as usize));
+    format!("ilo_{}", name.trim().replace(' ', "_"))
+}
+
+/// Writes the C for a compiled program.
+struct Emitter<'a> {
+    path: &'a str,
+    /// The text of the file, used to find lines and columns.
+    source: &'a str,
+    /// The text the program was parsed from, if it isn't `source`.
+    transliteration: Option<&'a Transliteration>,
+    /// The body of `main`.
+    body: String,
+    /// Spans of the values on the stack.
+    stack: Vec<SrcSpan>,
+    /// The most values on the stack at once.
+    max_depth: usize,
+}
+impl<'a> Emitter<'a> {
+    /// Finds where a span starts, as a C initialiser for `ilo_at`.
+    fn at(&self, span: SrcSpan) -> String {
+        let located = match self.transliteration {
+            Some(transliteration) => {
+                let range = transliteration.source_range(span.offset..span.offset + span.len);
+                SrcSpan {
+                    offset: range.start,
+                    len: range.len(),
+                }
+                .locate(self.source)
+            }
+            None => span.locate(self.source),
+        };
+        format!(
+            "{{{}, {}}}",
+            located.location_line(),
+            located.get_utf8_column()
+        )
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        self.body.push_str("    ");
+        self.body.push_str(text.as_ref());
+        self.body.push('\n');
+    }
+
+    fn push(&mut self, span: SrcSpan) -> usize {
+        self.stack.push(span);
+        self.max_depth = self.max_depth.max(self.stack.len());
+        self.stack.len() - 1
+    }
+
+    /// Pops values, returning the index of the first.
+    fn pop(&mut self, count: usize) -> usize {
+        let start = self.stack.len() - count;
+        self.stack.truncate(start);
+        start
+    }
+
+    /// Writes the definitions that the runtime needs.
+    fn definitions(&self, out: &mut String) {
+        writeln!(out, "#define ILO_PATH {}", c_string(self.path)).unwrap();
+        for (name, message) in MESSAGES {
+            writeln!(out, "#define {} {}", name, c_string(message)).unwrap();
+        }
+        for (name, word) in WORDS {
+            writeln!(out, "#define {} 0x{:X}u", name, *word as u32).unwrap();
+        }
+        for ptype in [
+            PrimitiveType::Toki,
+            PrimitiveType::Nanpa,
+            PrimitiveType::Lon,
+        ] {
+            let name = c_type(ptype).replace("ILO_", "ILO_TYPE_");
+            writeln!(out, "#define {} {}", name, c_string(&ptype.to_string())).unwrap();
+        }
+    }
+
+    /// Writes one instruction.
+    fn op(&mut self, chunk: &Chunk, op: Op, span: SrcSpan) {
+        let at = self.at(span);
+        match op {
+            Op::Const(index) => {
+                let i = self.push(span);
+                let value = c_literal(&chunk.constants[index as usize]);
+                self.line(format!("stack[{}] = {};", i, value));
+            }
+            Op::Declare(slot, ptype) => self.line(format!(
+                "ilo_declare(&slots[{}], {}, (ilo_at){});",
+                slot,
+                c_type(ptype),
+                at
+            )),
+            Op::Load(slot) => {
+                let i = self.push(span);
+                self.line(format!(
+                    "stack[{}] = ilo_load(&slots[{}], (ilo_at){});",
+                    i, slot, at
+                ));
+            }
+            Op::Store(slot) => {
+                let i = self.pop(1);
+                self.line(format!(
+                    "ilo_store(&slots[{}], stack[{}], (ilo_at){});",
+                    slot, i, at
+                ));
+            }
+            Op::Forget(start, end) => self.line(format!("ilo_forget(slots, {}, {});", start, end)),
+            Op::LoadNi(ptype) => {
+                let i = self.push(span);
+                self.line(format!(
+                    "stack[{}] = ilo_load_ni({}, (ilo_at){});",
+                    i,
+                    c_type(ptype),
+                    at
+                ));
+            }
+            Op::StoreNi => {
+                let i = self.pop(1);
+                self.line(format!("ilo_store_ni(stack[{}]);", i));
+            }
+            Op::Intrinsic(index, count) => {
+                let args: Vec<_> = self.stack[self.stack.len() - count as usize..]
+                    .iter()
+                    .map(|&span| self.at(span))
+                    .collect();
+                let i = self.pop(count as usize);
+                self.line(format!(
+                    "ilo_store_ni({}(&stack[{}], (const ilo_at[]){{{}}}, {}));",
+                    c_intrinsic(index),
+                    i,
+                    args.join(", "),
+                    count
+                ));
+                self.line(format!("ilo_drop_all(&stack[{}], {});", i, count));
+            }
+            Op::Write(count) => {
+                let i = self.pop(count as usize);
+                self.line(format!(
+                    "ilo_write(&stack[{}], {}, (ilo_at){});",
+                    i, count, at
+                ));
+            }
+            Op::WriteLine => self.line(format!("ilo_write_line((ilo_at){});", at)),
+            Op::ReadLine => self.line(format!("ilo_store_ni(ilo_read_line((ilo_at){}));", at)),
+            Op::Equal => {
+                let i = self.pop(2);
+                self.push(span);
+                self.line(format!(
+                    "stack[{}] = ilo_equal(&stack[{}], &stack[{}]);",
+                    i,
+                    i,
+                    i + 1
+                ));
+            }
+            Op::Greater | Op::Less => {
+                let i = self.pop(2);
+                self.push(span);
+                let comparison = if op == Op::Greater { ">" } else { "<" };
+                self.line(format!(
+                    "stack[{}] = ilo_lon_new(stack[{}].as.nanpa {} stack[{}].as.nanpa);",
+                    i,
+                    i,
+                    comparison,
+                    i + 1
+                ));
+            }
+            Op::AndEqual(count) | Op::OrEqual(count) => {
+                let i = self.pop(count as usize + 1);
+                self.push(span);
+                let function = match op {
+                    Op::AndEqual(_) => "ilo_and_equal",
+                    _ => "ilo_or_equal",
+                };
+                self.line(format!(
+                    "stack[{}] = {}(&stack[{}], {});",
+                    i, function, i, count
+                ));
+            }
+            Op::Jump(label) => self.line(format!("goto l{};", label)),
+            Op::JumpIfFalse(label) => {
+                let i = self.pop(1);
+                self.line(format!("if (!stack[{}].as.lon) goto l{};", i, label));
+            }
+            Op::Fail(index) => {
+                let err = &chunk.errors[index as usize];
+                let code = match self.transliteration {
+                    Some(transliteration) => transliteration.text.as_str(),
+                    None => self.source,
+                };
+                let located = err.locate(code);
+                let help = located.help().map_or("NULL".to_string(), c_string);
+                let at = self.at(err.span());
+                self.line(format!(
+                    "ilo_fail((ilo_at){}, {}, {});",
+                    at,
+                    c_string(located.reason()),
+                    help
+                ));
+            }
+        }
+    }
+
+    /// Writes the body of `main`.
+    fn program(&mut self, chunk: &Chunk) {
+        let mut targets: Vec<Label> = chunk
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Jump(label) | Op::JumpIfFalse(label) => Some(*label),
+                _ => None,
+            })
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+
+        // code after a jump or a failure only runs if something jumps to it, and the stack is
+        // always empty where jumps land
+        let mut reachable = true;
+        for (i, (&op, &span)) in chunk.ops.iter().zip(&chunk.spans).enumerate() {
+            if targets.binary_search(&(i as Label)).is_ok() {
+                self.body.push_str(&format!("l{}:\n", i));
+                self.stack.clear();
+                reachable = true;
+            }
+            if !reachable {
+                continue;
+            }
+            self.op(chunk, op, span);
+            reachable = !matches!(op, Op::Jump(_) | Op::Fail(_));
+        }
+        if targets.binary_search(&(chunk.ops.len() as Label)).is_ok() {
+            self.body.push_str(&format!("l{}:\n", chunk.ops.len()));
+        }
+    }
+}
+
+/// Compiles a program to C. `source` is the text of the file, and `transliteration` is how it
+/// was transliterated if it was written in sitelen Lasina; they are used to show where errors
+/// happen.
+pub(crate) fn to_c(
+    program: &[Statement],
+    path: &str,
+    source: &str,
+    transliteration: Option<&Transliteration>,
+) -> String {
+    let chunk = vm::compile(program);
+    let mut emitter = Emitter {
+        path,
+        source,
+        transliteration,
+        body: String::new(),
+        stack: Vec::new(),
+        max_depth: 0,
+    };
+    emitter.program(&chunk);
+
+    let mut out = String::from("/* Generated by o-ilo build from a sitelen ilo program. */\n\n");
+    emitter.definitions(&mut out);
+    out.push('\n');
+    out.push_str(RUNTIME);
+    out.push_str("\nint main(void) {\n");
+    if chunk.slots > 0 {
+        writeln!(out, "    static ilo_slot slots[{}];", chunk.slots).unwrap();
+    }
+    if emitter.max_depth > 0 {
+        // values can be pushed and never used, if the program fails before using them
+        writeln!(out, "    ilo_value stack[{}];", emitter.max_depth).unwrap();
+        out.push_str("    (void)stack;\n");
+    }
+    out.push_str(&emitter.body);
+    out.push_str("    return EXIT_SUCCESS;\n}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        process::{Command, Output, Stdio},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use sitelen_ilo_macros::sp;
+
+    use crate::{
+        parse::parse_program,
+        runtime::{Runtime, io::Streams},
+        transpile::c::to_c,
+    };
+
+    const PATH: &str = "test.lipu";
+
+    /// Builds a program with the system C compiler and runs it. Returns `None` if there is no C
+    /// compiler.
+    fn build_and_run(source: &str, input: &str) -> Option<Output> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let program = parse_program(source).expect("program should parse");
+        let dir = std::env::temp_dir().join(format!(
+            "sitelen-ilo-c-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (c_path, exe_path) = (dir.join("program.c"), dir.join("program"));
+        std::fs::write(&c_path, to_c(&program, PATH, source, None)).unwrap();
+
+        let cc = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Wextra", "-pedantic", "-Werror", "-o"])
+            .arg(&exe_path)
+            .arg(&c_path)
+            .output();
+        let cc = match cc {
+            Ok(cc) => cc,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(err) => panic!("failed to run cc: {}", err),
+        };
+        assert!(
+            cc.status.success(),
+            "cc failed:\n{}",
+            String::from_utf8_lossy(&cc.stderr)
+        );
+
+        let mut child = Command::new(&exe_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // the program can exit without reading all of its input
+        let written = child.stdin.take().unwrap().write_all(input.as_bytes());
+        if let Err(err) = written
+            && err.kind() != std::io::ErrorKind::BrokenPipe
+        {
+            panic!("failed to write input: {}", err);
+        }
+        let output = child.wait_with_output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        Some(output)
+    }
+
+    /// Checks that a compiled program prints the same output as the interpreter, and fails with
+    /// the same error.
+    fn check(source: &str, input: &str) {
+        let Some(output) = build_and_run(source, input) else {
+            eprintln!("skipping: no C compiler found");
+            return;
+        };
+
+        let program = parse_program(source).expect("program should parse");
+        let mut expected = Vec::new();
+        let result = Runtime::with_console(Streams {
+            input: input.as_bytes(),
+            output: &mut expected,
+        })
+        .run(&program);
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&expected),
+            "output differs for {:?} (stderr: {})",
+            source,
+            stderr
+        );
+        match result {
+            Ok(()) => assert!(output.status.success(), "program failed: {}", stderr),
+            Err(err) => {
+                let err = err.locate(source);
+                let span = err.span();
+                assert_eq!(output.status.code(), Some(1));
+                assert!(
+                    stderr.starts_with(&format!("error: {}\n", err.reason())),
+                    "wrong error for {:?}: {}",
+                    source,
+                    stderr
+                );
+                let location = format!(
+                    "--> {}:{}:{}\n",
+                    PATH,
+                    span.location_line(),
+                    span.get_utf8_column()
+                );
+                assert!(stderr.contains(&location), "wrong location: {}", stderr);
+            }
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        check(include_str!("../../../examples/musi_nanpa.lipu"), "");
+    }
+
+    #[test]
+    fn test_programs() {
+        let programs = [
+            // arithmetic, and writing numbers
+            [
+                sp!("o wan e nanpa <mute> e nanpa <luka tu>"),
+                sp!("ilo o toki e nanpa ni"),
+                sp!("o weka e nanpa <tu> tan nanpa <ale ale wan>"),
+                sp!("ilo o toki e nanpa ni"),
+                sp!("o weka e nanpa ni tan nanpa <ala>"),
+                sp!("ilo o toki e nanpa ni"),
+                sp!("o mute e nanpa ni e nanpa <luka>"),
+                sp!("o kipisi e nanpa ni tawa nanpa <tu>"),
+                sp!("o pana kipisi e nanpa ni kepeken nanpa <mute>"),
+                sp!("ilo o toki e nanpa ni e lon <lon> e lon <ala>"),
+                sp!("ilo o pini linja"),
+            ]
+            .join("\n"),
+            // strings
+            [
+                sp!("o wan linja e toki <jan> e toki <pona>"),
+                sp!("toki [ijo] o sin o toki ni"),
+                sp!("o jo nimi e nanpa <wan> tan toki [ijo]"),
+                sp!("ilo o toki e toki ni"),
+                sp!("o jo linja e nanpa <ala> tawa nanpa <tu> tan toki [ijo]"),
+                sp!("ilo o toki e toki ni"),
+                sp!("o nanpa nimi e toki <a>"),
+                sp!("o nimi nanpa e nanpa ni"),
+                sp!("ilo o toki e toki ni e toki <<<>>>"),
+                sp!("o ante toki e nanpa <ale mute weka>"),
+                sp!("o ante nanpa e toki ni"),
+                sp!("ilo o toki e nanpa ni"),
+                sp!("o ante lon e toki <lon>"),
+                sp!("o ante toki e lon ni"),
+                sp!("ilo o toki e toki ni"),
+            ]
+            .join("\n"),
+            // blocks, loops and scopes
+            [
+                sp!("nanpa [ijo] o sin"),
+                sp!("nanpa [ijo] li lili tawa nanpa <luka luka> la o sike"),
+                sp!("nanpa [jan] o sin o nanpa [ijo]"),
+                sp!("o wan e nanpa [ijo] e nanpa <wan>"),
+                sp!("nanpa [ijo] o nanpa ni"),
+                sp!("nanpa [ijo] li nanpa <tu wan> la sike o sin"),
+                sp!("o pana kipisi e nanpa [ijo] kepeken nanpa <tu>"),
+                sp!("nanpa ni li nanpa <ala> la o pali"),
+                sp!("ilo o toki e toki <tu>"),
+                sp!("ala la nanpa [jan] li suli tawa nanpa <luka tu> la o pali"),
+                sp!("sike o pini"),
+                sp!("ala la o pali"),
+                sp!("ilo o toki e nanpa [jan]"),
+                sp!("pini"),
+                sp!("ken la lon <ala> anu lon <lon> li lon <lon>"),
+                sp!("lon ni en lon <lon> li lon <lon> la ilo o pini linja"),
+                sp!("pini"),
+            ]
+            .join("\n"),
+            // console input
+            [
+                sp!("ilo o wile linja"),
+                sp!("ilo o toki e toki ni e toki <:>"),
+                sp!("ilo o wile linja"),
+                sp!("ilo o toki e toki ni e toki <:>"),
+                sp!("ilo o wile linja"),
+                sp!("ilo o toki e toki ni e toki <:>"),
+            ]
+            .join("\n"),
+        ];
+        for program in &programs {
+            check(program, concat!(sp!("pona"), "\r\n", "abc"));
+        }
+    }
+
+    #[test]
+    fn test_failures() {
+        let programs = [
+            sp!("nanpa [ijo] o nanpa <wan>").to_string(),
+            [sp!("nanpa [ijo] o sin"), sp!("nanpa [ijo] o sin")].join("\n"),
+            sp!("ilo o toki e nanpa ni").to_string(),
+            [sp!("o sona e toki <wan>"), sp!("ilo o toki e nanpa ni")].join("\n"),
+            sp!("o pana kipisi e nanpa <wan> kepeken nanpa <ala>").to_string(),
+            sp!("o mute e nanpa <ale ale ale ale ale ale ale ale ale ale> e nanpa <ale>")
+                .to_string(),
+            sp!("o jo nimi e nanpa <tu> tan toki <a>").to_string(),
+            sp!("o jo linja e nanpa <tu> tawa nanpa <wan> tan toki <a a>").to_string(),
+            sp!("o ante nanpa e toki <weka wan>").to_string(),
+            sp!("o ante lon e toki <ala ala>").to_string(),
+            sp!("o nimi nanpa e nanpa <wan weka>").to_string(),
+            sp!("o moku e nanpa <wan>").to_string(),
+            sp!("ken la nanpa <wan> li suli tawa toki <wan>").to_string(),
+        ];
+        for program in &programs {
+            check(program, "");
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Compiling programs to other languages, so they can be built without the interpreter.
+
+pub(crate) mod c;
@@ -0,0 +1,756 @@
+/*
+ * Runtime for sitelen ilo programs compiled to C.
+ *
+ * This is copied into every generated file, after the definitions it needs (ILO_PATH, the error
+ * messages, the words used to write values, and the names of types). It mirrors the behaviour of
+ * the interpreter, including its error messages.
+ */
+
+#include <errno.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#if defined(__GNUC__) || defined(__clang__)
+#define ILO_UNUSED __attribute__((unused))
+#else
+#define ILO_UNUSED
+#endif
+
+/* Where an error happened in the source. */
+typedef struct {
+    int line;
+    int column;
+} ilo_at;
+
+#define ILO_AT(line, column) ((ilo_at){(line), (column)})
+
+/* A UTF-8 string. Strings are owned by the value holding them. */
+typedef struct {
+    char *data;
+    size_t len;
+} ilo_toki;
+
+typedef enum { ILO_TOKI, ILO_NANPA, ILO_LON } ilo_type;
+
+/* A value of any type. */
+typedef struct {
+    ilo_type type;
+    union {
+        ilo_toki toki;
+        int64_t nanpa;
+        bool lon;
+    } as;
+} ilo_value;
+
+/* A variable, or ni. A slot that isn't declared (or a ni that is empty) holds no value. */
+typedef struct {
+    ilo_value value;
+    bool declared;
+} ilo_slot;
+
+static const char *const ilo_type_names[] = {ILO_TYPE_TOKI, ILO_TYPE_NANPA, ILO_TYPE_LON};
+
+/* The last result of an operation. */
+static ilo_slot ilo_ni;
+
+/* Prints an error in the same way as the interpreter, and exits. */
+ILO_UNUSED static void ilo_fail(ilo_at at, const char *reason, const char *help) {
+    int width = 1;
+    int line;
+    for (line = at.line; line >= 10; line /= 10) {
+        width++;
+    }
+
+    fflush(stdout);
+    fprintf(stderr, "error: %s\n", reason);
+    fprintf(stderr, "%*s--> %s:%d:%d\n", width, "", ILO_PATH, at.line, at.column);
+    if (help != NULL) {
+        fprintf(stderr, "%*s = help: %s\n", width, "", help);
+    }
+    exit(EXIT_FAILURE);
+}
+
+/* Fails with an error from the system, such as a failed write. */
+ILO_UNUSED static void ilo_fail_errno(ilo_at at, const char *reason) {
+    char message[256];
+    int code = errno;
+    snprintf(message, sizeof message, "%s: %s (os error %d)", reason, strerror(code), code);
+    ilo_fail(at, message, NULL);
+}
+
+ILO_UNUSED static void *ilo_alloc(size_t size) {
+    void *data = malloc(size > 0 ? size : 1);
+    if (data == NULL) {
+        fputs("error: out of memory\n", stderr);
+        exit(EXIT_FAILURE);
+    }
+    return data;
+}
+
+/* Makes a string from a copy of some bytes. */
+ILO_UNUSED static ilo_value ilo_toki_new(const char *data, size_t len) {
+    ilo_value value;
+    value.type = ILO_TOKI;
+    value.as.toki.data = ilo_alloc(len);
+    value.as.toki.len = len;
+    if (len > 0) {
+        memcpy(value.as.toki.data, data, len);
+    }
+    return value;
+}
+
+ILO_UNUSED static ilo_value ilo_nanpa_new(int64_t nanpa) {
+    ilo_value value;
+    value.type = ILO_NANPA;
+    value.as.nanpa = nanpa;
+    return value;
+}
+
+ILO_UNUSED static ilo_value ilo_lon_new(bool lon) {
+    ilo_value value;
+    value.type = ILO_LON;
+    value.as.lon = lon;
+    return value;
+}
+
+ILO_UNUSED static ilo_value ilo_copy(const ilo_value *value) {
+    if (value->type == ILO_TOKI) {
+        return ilo_toki_new(value->as.toki.data, value->as.toki.len);
+    }
+    return *value;
+}
+
+ILO_UNUSED static void ilo_drop(ilo_value *value) {
+    if (value->type == ILO_TOKI) {
+        free(value->as.toki.data);
+        value->as.toki.data = NULL;
+    }
+}
+
+ILO_UNUSED static void ilo_drop_all(ilo_value *values, int count) {
+    int i;
+    for (i = 0; i < count; i++) {
+        ilo_drop(&values[i]);
+    }
+}
+
+/* ---- UTF-8 ---- */
+
+/* Gets the length of the character starting with a byte. */
+ILO_UNUSED static size_t ilo_utf8_length(unsigned char byte) {
+    if (byte < 0x80) {
+        return 1;
+    } else if (byte < 0xE0) {
+        return 2;
+    } else if (byte < 0xF0) {
+        return 3;
+    }
+    return 4;
+}
+
+/* Decodes the character at the start of some valid UTF-8, returning its length. */
+ILO_UNUSED static size_t ilo_utf8_decode(const char *data, uint32_t *c) {
+    const unsigned char *bytes = (const unsigned char *)data;
+    size_t len = ilo_utf8_length(bytes[0]);
+    size_t i;
+    static const unsigned char masks[] = {0, 0x7F, 0x1F, 0x0F, 0x07};
+    *c = bytes[0] & masks[len];
+    for (i = 1; i < len; i++) {
+        *c = (*c << 6) | (bytes[i] & 0x3F);
+    }
+    return len;
+}
+
+/* Encodes a character, returning its length. `out` must have room for 4 bytes. */
+ILO_UNUSED static size_t ilo_utf8_encode(uint32_t c, char *out) {
+    if (c < 0x80) {
+        out[0] = (char)c;
+        return 1;
+    } else if (c < 0x800) {
+        out[0] = (char)(0xC0 | (c >> 6));
+        out[1] = (char)(0x80 | (c & 0x3F));
+        return 2;
+    } else if (c < 0x10000) {
+        out[0] = (char)(0xE0 | (c >> 12));
+        out[1] = (char)(0x80 | ((c >> 6) & 0x3F));
+        out[2] = (char)(0x80 | (c & 0x3F));
+        return 3;
+    }
+    out[0] = (char)(0xF0 | (c >> 18));
+    out[1] = (char)(0x80 | ((c >> 12) & 0x3F));
+    out[2] = (char)(0x80 | ((c >> 6) & 0x3F));
+    out[3] = (char)(0x80 | (c & 0x3F));
+    return 4;
+}
+
+/* Checks that some bytes are valid UTF-8. */
+ILO_UNUSED static bool ilo_utf8_valid(const char *data, size_t len) {
+    const unsigned char *bytes = (const unsigned char *)data;
+    size_t i = 0;
+    while (i < len) {
+        unsigned char byte = bytes[i];
+        size_t n;
+        size_t j;
+        uint32_t c;
+        if (byte < 0x80) {
+            i++;
+            continue;
+        } else if (byte >= 0xC2 && byte < 0xE0) {
+            n = 2;
+        } else if (byte >= 0xE0 && byte < 0xF0) {
+            n = 3;
+        } else if (byte >= 0xF0 && byte < 0xF5) {
+            n = 4;
+        } else {
+            return false;
+        }
+        if (len - i < n) {
+            return false;
+        }
+        for (j = 1; j < n; j++) {
+            if ((bytes[i + j] & 0xC0) != 0x80) {
+                return false;
+            }
+        }
+        ilo_utf8_decode(data + i, &c);
+        /* overlong forms, surrogates and values past the last character */
+        if ((n == 3 && c < 0x800) || (c >= 0xD800 && c < 0xE000) || (n == 4 && c < 0x10000) ||
+            c > 0x10FFFF) {
+            return false;
+        }
+        i += n;
+    }
+    return true;
+}
+
+/* ---- Text ---- */
+
+/* A growable string, for building text. */
+typedef struct {
+    char *data;
+    size_t len;
+    size_t capacity;
+} ilo_buffer;
+
+ILO_UNUSED static void ilo_buffer_push(ilo_buffer *buffer, const char *data, size_t len) {
+    if (buffer->len + len > buffer->capacity) {
+        char *grown;
+        size_t capacity = buffer->capacity > 0 ? buffer->capacity * 2 : 64;
+        while (capacity < buffer->len + len) {
+            capacity *= 2;
+        }
+        grown = ilo_alloc(capacity);
+        if (buffer->len > 0) {
+            memcpy(grown, buffer->data, buffer->len);
+        }
+        free(buffer->data);
+        buffer->data = grown;
+        buffer->capacity = capacity;
+    }
+    if (len > 0) {
+        memcpy(buffer->data + buffer->len, data, len);
+    }
+    buffer->len += len;
+}
+
+ILO_UNUSED static void ilo_buffer_push_char(ilo_buffer *buffer, uint32_t c) {
+    char bytes[4];
+    size_t len = ilo_utf8_encode(c, bytes);
+    ilo_buffer_push(buffer, bytes, len);
+}
+
+/* Turns a buffer into a string, taking its contents. */
+ILO_UNUSED static ilo_value ilo_buffer_finish(ilo_buffer *buffer) {
+    ilo_value value;
+    value.type = ILO_TOKI;
+    value.as.toki.data = buffer->data != NULL ? buffer->data : ilo_alloc(0);
+    value.as.toki.len = buffer->len;
+    return value;
+}
+
+/* Writes a number in canonical nasin nanpa pona. */
+ILO_UNUSED static void ilo_write_nanpa(ilo_buffer *out, int64_t value) {
+    unsigned groups[10];
+    int count = 0;
+    int i;
+    uint64_t magnitude = value < 0 ? (uint64_t)0 - (uint64_t)value : (uint64_t)value;
+
+    if (value == 0) {
+        ilo_buffer_push_char(out, ILO_WORD_ALA);
+        return;
+    }
+    while (magnitude > 0) {
+        groups[count++] = (unsigned)(magnitude % 100);
+        magnitude /= 100;
+    }
+    for (i = count - 1; i >= 0; i--) {
+        unsigned group = groups[i];
+        unsigned j;
+        if (i < count - 1) {
+            ilo_buffer_push_char(out, ILO_WORD_ALE);
+        }
+        for (j = 0; j < group / 20; j++) {
+            ilo_buffer_push_char(out, ILO_WORD_MUTE);
+        }
+        for (j = 0; j < group % 20 / 5; j++) {
+            ilo_buffer_push_char(out, ILO_WORD_LUKA);
+        }
+        for (j = 0; j < group % 5 / 2; j++) {
+            ilo_buffer_push_char(out, ILO_WORD_TU);
+        }
+        for (j = 0; j < group % 5 % 2; j++) {
+            ilo_buffer_push_char(out, ILO_WORD_WAN);
+        }
+    }
+    if (value < 0) {
+        ilo_buffer_push_char(out, ILO_WORD_WEKA);
+    }
+}
+
+/* Writes a value as text, as it would be printed. */
+ILO_UNUSED static void ilo_write_value(ilo_buffer *out, const ilo_value *value) {
+    switch (value->type) {
+    case ILO_TOKI:
+        ilo_buffer_push(out, value->as.toki.data, value->as.toki.len);
+        break;
+    case ILO_NANPA:
+        ilo_write_nanpa(out, value->as.nanpa);
+        break;
+    case ILO_LON:
+        ilo_buffer_push_char(out, value->as.lon ? ILO_WORD_LON : ILO_WORD_ALA);
+        break;
+    }
+}
+
+/* Reads a whole string as nasin nanpa pona, as the parser does. */
+ILO_UNUSED static int64_t ilo_nanpa_from_text(const ilo_toki *text, ilo_at at) {
+    /* the magnitude saturates just past the range of nanpa */
+    const uint64_t limit = (uint64_t)INT64_MAX + 2;
+    uint64_t magnitude = 0;
+    bool expect_end = false;
+    bool add_ale = false;
+    bool negative = false;
+    size_t end = text->len;
+    size_t i = 0;
+
+    while (i < text->len) {
+        uint32_t c;
+        size_t len = ilo_utf8_decode(text->data + i, &c);
+        uint64_t add = 0;
+        if (i == 0) {
+            if (c == ILO_WORD_ALA) {
+                expect_end = true;
+                i += len;
+                continue;
+            }
+            if (c == ILO_WORD_WEKA) {
+                ilo_fail(at, ILO_ERR_NNP_FAILED_MATCH, NULL);
+            }
+            add_ale = c == ILO_WORD_ALE;
+        }
+        if (expect_end) {
+            end = i;
+            break;
+        }
+        if (c == ILO_WORD_ALE && !add_ale) {
+            magnitude = magnitude > limit / 100 ? limit : magnitude * 100;
+        } else if (c == ILO_WORD_ALE) {
+            add = 100;
+        } else if (c == ILO_WORD_MUTE) {
+            add = 20;
+        } else if (c == ILO_WORD_LUKA) {
+            add = 5;
+        } else if (c == ILO_WORD_TU) {
+            add = 2;
+        } else if (c == ILO_WORD_WAN) {
+            add = 1;
+        } else if (c == ILO_WORD_WEKA) {
+            negative = true;
+            expect_end = true;
+        } else {
+            end = i;
+            break;
+        }
+        magnitude = magnitude > limit - add ? limit : magnitude + add;
+        i += len;
+    }
+
+    if (magnitude > (uint64_t)INT64_MAX + (negative ? 1 : 0)) {
+        ilo_fail(at, ILO_ERR_NNP_TOO_LARGE, NULL);
+    }
+    if (end != text->len || text->len == 0) {
+        ilo_fail(at, ILO_ERR_NNP_FAILED_MATCH, NULL);
+    }
+    if (negative) {
+        return magnitude > (uint64_t)INT64_MAX ? INT64_MIN : -(int64_t)magnitude;
+    }
+    return (int64_t)magnitude;
+}
+
+/* Reads a whole string as a lon value. */
+ILO_UNUSED static bool ilo_lon_from_text(const ilo_toki *text, ilo_at at) {
+    uint32_t c = 0;
+    if (text->len > 0 && ilo_utf8_decode(text->data, &c) == text->len) {
+        if (c == ILO_WORD_LON) {
+            return true;
+        } else if (c == ILO_WORD_ALA) {
+            return false;
+        }
+    }
+    ilo_fail(at, ILO_ERR_FAILED_LON_MATCH, NULL);
+    return false;
+}
+
+/* ---- Variables and ni ---- */
+
+ILO_UNUSED static void ilo_declare(ilo_slot *slot, ilo_type type, ilo_at at) {
+    if (slot->declared) {
+        ilo_fail(at, ILO_ERR_REDECLARED, NULL);
+    }
+    switch (type) {
+    case ILO_TOKI:
+        slot->value = ilo_toki_new(NULL, 0);
+        break;
+    case ILO_NANPA:
+        slot->value = ilo_nanpa_new(0);
+        break;
+    case ILO_LON:
+        slot->value = ilo_lon_new(false);
+        break;
+    }
+    slot->declared = true;
+}
+
+ILO_UNUSED static ilo_value ilo_load(const ilo_slot *slot, ilo_at at) {
+    if (!slot->declared) {
+        ilo_fail(at, ILO_ERR_UNDECLARED, ILO_HELP_UNDECLARED);
+    }
+    return ilo_copy(&slot->value);
+}
+
+ILO_UNUSED static void ilo_store(ilo_slot *slot, ilo_value value, ilo_at at) {
+    if (!slot->declared) {
+        ilo_fail(at, ILO_ERR_UNDECLARED, ILO_HELP_UNDECLARED);
+    }
+    ilo_drop(&slot->value);
+    slot->value = value;
+}
+
+/* Undeclares a range of slots, when a block ends. */
+ILO_UNUSED static void ilo_forget(ilo_slot *slots, int start, int end) {
+    int i;
+    for (i = start; i < end; i++) {
+        if (slots[i].declared) {
+            ilo_drop(&slots[i].value);
+            slots[i].declared = false;
+        }
+    }
+}
+
+ILO_UNUSED static ilo_value ilo_load_ni(ilo_type type, ilo_at at) {
+    if (!ilo_ni.declared) {
+        ilo_fail(at, ILO_ERR_NI_EMPTY, NULL);
+    }
+    if (ilo_ni.value.type != type) {
+        char message[256];
+        snprintf(message, sizeof message, "ni holds a %s value, not %s",
+                 ilo_type_names[ilo_ni.value.type], ilo_type_names[type]);
+        ilo_fail(at, message, NULL);
+    }
+    return ilo_copy(&ilo_ni.value);
+}
+
+ILO_UNUSED static void ilo_store_ni(ilo_value value) {
+    if (ilo_ni.declared) {
+        ilo_drop(&ilo_ni.value);
+    }
+    ilo_ni.value = value;
+    ilo_ni.declared = true;
+}
+
+/* ---- Console ---- */
+
+/* Writes values to stdout, and drops them. */
+ILO_UNUSED static void ilo_write(ilo_value *values, int count, ilo_at at) {
+    ilo_buffer text = {NULL, 0, 0};
+    int i;
+    for (i = 0; i < count; i++) {
+        ilo_write_value(&text, &values[i]);
+    }
+    ilo_drop_all(values, count);
+    if (text.len > 0 && fwrite(text.data, 1, text.len, stdout) != text.len) {
+        ilo_fail_errno(at, ILO_ERR_WRITE);
+    }
+    free(text.data);
+}
+
+ILO_UNUSED static void ilo_write_line(ilo_at at) {
+    if (putchar('\n') == EOF) {
+        ilo_fail_errno(at, ILO_ERR_WRITE);
+    }
+}
+
+/* Reads a line from stdin, without its line break. The end of input reads as an empty line. */
+ILO_UNUSED static ilo_value ilo_read_line(ilo_at at) {
+    ilo_buffer line = {NULL, 0, 0};
+    int c;
+
+    if (fflush(stdout) == EOF) {
+        ilo_fail_errno(at, ILO_ERR_READ);
+    }
+    while ((c = getchar()) != EOF) {
+        char byte = (char)c;
+        if (c == '\n') {
+            if (line.len > 0 && line.data[line.len - 1] == '\r') {
+                line.len--;
+            }
+            break;
+        }
+        ilo_buffer_push(&line, &byte, 1);
+    }
+    if (ferror(stdin)) {
+        ilo_fail_errno(at, ILO_ERR_READ);
+    }
+    if (!ilo_utf8_valid(line.data, line.len)) {
+        char message[256];
+        snprintf(message, sizeof message, "%s: stream did not contain valid UTF-8", ILO_ERR_READ);
+        ilo_fail(at, message, NULL);
+    }
+    return ilo_buffer_finish(&line);
+}
+
+/* ---- Conditions ---- */
+
+/* Checks if two values are equal, and drops them. */
+ILO_UNUSED static ilo_value ilo_equal(ilo_value *a, ilo_value *b) {
+    bool equal = false;
+    switch (a->type) {
+    case ILO_TOKI:
+        equal = a->as.toki.len == b->as.toki.len &&
+                (a->as.toki.len == 0 || memcmp(a->as.toki.data, b->as.toki.data, a->as.toki.len) == 0);
+        break;
+    case ILO_NANPA:
+        equal = a->as.nanpa == b->as.nanpa;
+        break;
+    case ILO_LON:
+        equal = a->as.lon == b->as.lon;
+        break;
+    }
+    ilo_drop(a);
+    ilo_drop(b);
+    return ilo_lon_new(equal);
+}
+
+/* Checks whether `ps` together are equal to the value after them. */
+ILO_UNUSED static ilo_value ilo_and_equal(const ilo_value *ps, int count) {
+    bool p = true;
+    int i;
+    for (i = 0; i < count; i++) {
+        p = p && ps[i].as.lon;
+    }
+    return ilo_lon_new(p == ps[count].as.lon);
+}
+
+/* Checks whether any of `ps` are equal to the value after them. */
+ILO_UNUSED static ilo_value ilo_or_equal(const ilo_value *ps, int count) {
+    bool p = false;
+    int i;
+    for (i = 0; i < count; i++) {
+        p = p || ps[i].as.lon;
+    }
+    return ilo_lon_new(p == ps[count].as.lon);
+}
+
+/* ---- Intrinsics ---- */
+
+/* Each intrinsic takes its arguments, where they are in the source, and how many there are. The
+ * arguments are left for the caller to drop. */
+
+ILO_UNUSED static int64_t ilo_checked_add(int64_t a, int64_t b, ilo_at at) {
+    if ((b > 0 && a > INT64_MAX - b) || (b < 0 && a < INT64_MIN - b)) {
+        ilo_fail(at, ILO_ERR_OVERFLOW, NULL);
+    }
+    return a + b;
+}
+
+ILO_UNUSED static int64_t ilo_checked_sub(int64_t a, int64_t b, ilo_at at) {
+    if ((b < 0 && a > INT64_MAX + b) || (b > 0 && a < INT64_MIN + b)) {
+        ilo_fail(at, ILO_ERR_OVERFLOW, NULL);
+    }
+    return a - b;
+}
+
+ILO_UNUSED static int64_t ilo_checked_mul(int64_t a, int64_t b, ilo_at at) {
+    bool overflow;
+    if (a > 0) {
+        overflow = b > 0 ? a > INT64_MAX / b : b < INT64_MIN / a;
+    } else {
+        overflow = b > 0 ? a < INT64_MIN / b : a != 0 && b < INT64_MAX / a;
+    }
+    if (overflow) {
+        ilo_fail(at, ILO_ERR_OVERFLOW, NULL);
+    }
+    return a * b;
+}
+
+/* Finds the byte offset of a character in a string. The length of the string is a valid offset. */
+ILO_UNUSED static size_t ilo_char_offset(const ilo_toki *text, int64_t index, ilo_at at) {
+    size_t offset = 0;
+    int64_t i;
+    if (index < 0) {
+        ilo_fail(at, ILO_ERR_INDEX_RANGE, NULL);
+    }
+    for (i = 0; i < index; i++) {
+        if (offset >= text->len) {
+            ilo_fail(at, ILO_ERR_INDEX_RANGE, NULL);
+        }
+        offset += ilo_utf8_length((unsigned char)text->data[offset]);
+    }
+    return offset;
+}
+
+ILO_UNUSED static ilo_value ilo_sona(const ilo_value *args, const ilo_at *at, int count) {
+    (void)at;
+    (void)count;
+    return ilo_copy(&args[0]);
+}
+
+ILO_UNUSED static ilo_value ilo_wan(const ilo_value *args, const ilo_at *at, int count) {
+    int64_t result = 0;
+    int i;
+    for (i = 0; i < count; i++) {
+        result = ilo_checked_add(result, args[i].as.nanpa, at[i]);
+    }
+    return ilo_nanpa_new(result);
+}
+
+ILO_UNUSED static ilo_value ilo_weka(const ilo_value *args, const ilo_at *at, int count) {
+    int64_t result = args[count - 1].as.nanpa;
+    int i;
+    for (i = 0; i < count - 1; i++) {
+        result = ilo_checked_sub(result, args[i].as.nanpa, at[i]);
+    }
+    return ilo_nanpa_new(result);
+}
+
+ILO_UNUSED static ilo_value ilo_mute(const ilo_value *args, const ilo_at *at, int count) {
+    int64_t result = 1;
+    int i;
+    for (i = 0; i < count; i++) {
+        result = ilo_checked_mul(result, args[i].as.nanpa, at[i]);
+    }
+    return ilo_nanpa_new(result);
+}
+
+ILO_UNUSED static ilo_value ilo_kipisi(const ilo_value *args, const ilo_at *at, int count) {
+    int64_t result = args[0].as.nanpa;
+    int i;
+    for (i = 1; i < count; i++) {
+        int64_t n = args[i].as.nanpa;
+        if (n == 0) {
+            ilo_fail(at[i], ILO_ERR_DIV_ZERO, NULL);
+        }
+        if (result == INT64_MIN && n == -1) {
+            ilo_fail(at[i], ILO_ERR_OVERFLOW, NULL);
+        }
+        result /= n;
+    }
+    return ilo_nanpa_new(result);
+}
+
+ILO_UNUSED static ilo_value ilo_pana_kipisi(const ilo_value *args, const ilo_at *at, int count) {
+    int64_t k = args[0].as.nanpa;
+    int64_t n = args[1].as.nanpa;
+    (void)count;
+    if (n == 0) {
+        ilo_fail(at[1], ILO_ERR_DIV_ZERO, NULL);
+    }
+    return ilo_nanpa_new(n == -1 ? 0 : k % n);
+}
+
+ILO_UNUSED static ilo_value ilo_wan_linja(const ilo_value *args, const ilo_at *at, int count) {
+    ilo_buffer text = {NULL, 0, 0};
+    int i;
+    (void)at;
+    for (i = 0; i < count; i++) {
+        ilo_buffer_push(&text, args[i].as.toki.data, args[i].as.toki.len);
+    }
+    return ilo_buffer_finish(&text);
+}
+
+ILO_UNUSED static ilo_value ilo_jo_nimi(const ilo_value *args, const ilo_at *at, int count) {
+    const ilo_toki *text = &args[1].as.toki;
+    size_t start = ilo_char_offset(text, args[0].as.nanpa, at[0]);
+    (void)count;
+    if (start == text->len) {
+        ilo_fail(at[0], ILO_ERR_INDEX_RANGE, NULL);
+    }
+    return ilo_toki_new(text->data + start, ilo_utf8_length((unsigned char)text->data[start]));
+}
+
+ILO_UNUSED static ilo_value ilo_jo_linja(const ilo_value *args, const ilo_at *at, int count) {
+    const ilo_toki *text = &args[2].as.toki;
+    size_t start = ilo_char_offset(text, args[0].as.nanpa, at[0]);
+    size_t end = ilo_char_offset(text, args[1].as.nanpa, at[1]);
+    (void)count;
+    if (end < start) {
+        ilo_fail(at[1], ILO_ERR_SUBSTR_ORDER, NULL);
+    }
+    return ilo_toki_new(text->data + start, end - start);
+}
+
+ILO_UNUSED static ilo_value ilo_nanpa_nimi(const ilo_value *args, const ilo_at *at, int count) {
+    const ilo_toki *text = &args[0].as.toki;
+    uint32_t c = 0;
+    (void)count;
+    if (text->len == 0 || ilo_utf8_decode(text->data, &c) != text->len) {
+        ilo_fail(at[0], ILO_ERR_NOT_CHAR, NULL);
+    }
+    return ilo_nanpa_new(c);
+}
+
+ILO_UNUSED static ilo_value ilo_nimi_nanpa(const ilo_value *args, const ilo_at *at, int count) {
+    int64_t code = args[0].as.nanpa;
+    char bytes[4];
+    (void)count;
+    if (code < 0 || code > 0x10FFFF || (code >= 0xD800 && code < 0xE000)) {
+        ilo_fail(at[0], ILO_ERR_CODEPOINT, NULL);
+    }
+    return ilo_toki_new(bytes, ilo_utf8_encode((uint32_t)code, bytes));
+}
+
+ILO_UNUSED static ilo_value ilo_ante_nanpa(const ilo_value *args, const ilo_at *at, int count) {
+    (void)count;
+    switch (args[0].type) {
+    case ILO_TOKI:
+        return ilo_nanpa_new(ilo_nanpa_from_text(&args[0].as.toki, at[0]));
+    case ILO_LON:
+        return ilo_nanpa_new(args[0].as.lon ? 1 : 0);
+    default:
+        return ilo_copy(&args[0]);
+    }
+}
+
+ILO_UNUSED static ilo_value ilo_ante_lon(const ilo_value *args, const ilo_at *at, int count) {
+    (void)count;
+    switch (args[0].type) {
+    case ILO_TOKI:
+        return ilo_lon_new(ilo_lon_from_text(&args[0].as.toki, at[0]));
+    case ILO_NANPA:
+        return ilo_lon_new(args[0].as.nanpa != 0);
+    default:
+        return ilo_copy(&args[0]);
+    }
+}
+
+ILO_UNUSED static ilo_value ilo_ante_toki(const ilo_value *args, const ilo_at *at, int count) {
+    ilo_buffer text = {NULL, 0, 0};
+    (void)at;
+    (void)count;
+    ilo_write_value(&text, &args[0]);
+    return ilo_buffer_finish(&text);
+}